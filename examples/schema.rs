@@ -3,7 +3,7 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use fury::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use fury::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, ReceiveMsg};
 // use fury::state::State;
 
 fn main() {
@@ -14,7 +14,9 @@ fn main() {
 
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
     // export_schema(&schema_for!(State), &out_dir);
     // export_schema(&schema_for!(CountResponse), &out_dir);
 }
@@ -1,20 +1,59 @@
 use std::cmp;
+use std::convert::TryFrom;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{Raffle, COUNTER, RAFFLEMAP, ADMINS, Counter, JoinInfo};
-use cosmwasm_std::{StdResult, Deps, Binary, QueryRequest, BankQuery, to_binary, AllBalanceResponse, from_binary, WasmMsg, CosmosMsg};
+use crate::msg::{AcceptedAsset, EntryOdds, ExecuteMsg, InstantiateMsg, LeaderboardEntry, ListOrder, ListRafflesResponse, MigrateMsg, PlayerRaffle, QueryMsg, SimulateEntryResponse, TierOdds, RaffleListItem, RaffleStatus, RaffleHookMsg, ReceiveMsg, ReceiveNftMsg, TicketMetadata};
+use crate::state::{AssetAmount, ClaimConfig, DustDestination, PrizeTier, TierPrize, Entry, Sponsorship, NftPrize, PendingClaim, BiggestWin, GlobalStats, ArchivedEntry, PlayerStats, Raffle, RaffleAsset, RaffleSummary, RedrawRecord, Settlement, UnclaimedPrize, UnawardedPrize, JackpotConfig, JackpotHit, ARCHIVE, COUNTER, GLOBAL_STATS, LEADERBOARD, PLAYER_ARCHIVE, PLAYER_STATS, TREASURY, JACKPOT_CONFIGS, JACKPOTS, JACKPOT_HITS, RAFFLEMAP, ADMINS, ALLOWED_DENOMS, HOOKS, PAYOUT_COUNT, PENDING_PAYOUTS, FAILED_PAYOUTS, Payout, Counter, SERIES_POOLS, SPONSORSHIPS, TICKET_RANGES, LEGACY_RAFFLES, LegacyRaffle, entries, token_key};
+use cosmwasm_std::{attr, Attribute, Decimal, StdResult, StdError, Deps, Binary, Event, Order, Reply, Storage, SubMsg, SubMsgResult, QueryRequest, BankQuery, to_binary, AllBalanceResponse, from_binary, WasmMsg, CosmosMsg};
 
+use cw_storage_plus::Bound;
+use cw2::set_contract_version;
 use rand_core::{RngCore, SeedableRng};
 use crate::rand::{sha_256, Prng};
 use rand_chacha::ChaChaRng;
-use cw20::{Cw20ReceiveMsg, Cw20ExecuteMsg};
+use cw20::{Cw20ReceiveMsg, Cw20ExecuteMsg, Cw20QueryMsg, BalanceResponse, Denom};
+use cw721::{Cw721ExecuteMsg, Cw721QueryMsg, Cw721ReceiveMsg, OwnerOfResponse};
+use cw721_base::{ExecuteMsg as Cw721BaseExecuteMsg, MintMsg};
 
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
     entry_point, Addr, BankMsg, Coin, DepsMut, Env, MessageInfo, Response, Uint128,
 };
 
+// version info for migration
+const CONTRACT_NAME: &str = "crates.io:fury";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const HOOK_REPLY_ID: u64 = 1;
+// gas a hook may use, so that running out of gas fails the hook and not the raffle
+const HOOK_GAS_LIMIT: u64 = 300_000;
+// reply ids from here on are PAYOUT_REPLY_ID_BASE + payout id
+const PAYOUT_REPLY_ID_BASE: u64 = 1 << 32;
+
+// every ticket of a raffle with ticket NFTs is minted in the purchase transaction
+const MAX_NFT_TICKETS_PER_PURCHASE: u32 = 100;
+
+// ranks a raffle may have, every one of them is kept in the raffle and drawn at once
+const MAX_RANKS: u64 = 1_000;
+
+// tickets drawn when redrawing, each one skipped if its holder is excluded, before walking the entries instead
+const REDRAW_ATTEMPTS: u32 = 20;
+// entries that walk reads, enough to get past the few excluded players
+const REDRAW_SCAN_LIMIT: usize = 1_000;
+
+// raffles ListRaffles reads per page, however few match its filters
+const MAX_LIST_SCAN: usize = 100;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+// recipients paid per transaction when settling, EndRaffleRound and CancelRaffleRound pay the first batch
+const SETTLEMENT_LIMIT: u32 = 50;
+const MAX_SETTLEMENT_LIMIT: u32 = 200;
+
+// how long a settled raffle stays whole before ArchiveRaffle may compact it
+const ARCHIVE_RETENTION: u64 = 30 * 24 * 3600;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -27,9 +66,21 @@ pub fn instantiate(
         .into_iter()
         .map(|addr| deps.api.addr_validate(&addr))
         .collect();
-    ADMINS.save(deps.storage, &admins?)?;
-
-    Ok(Response::new())
+    let admins = admins?;
+    ADMINS.save(deps.storage, &admins)?;
+    ALLOWED_DENOMS.save(deps.storage, &msg.allowed_denoms)?;
+    let treasury = deps.api.addr_validate(&msg.treasury)?;
+    TREASURY.save(deps.storage, &treasury)?;
+    COUNTER.save(deps.storage, &Counter { counter: 0 })?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "instantiate")
+        .add_attribute("treasury", treasury)
+        .add_attributes(non_empty_attributes(vec![
+            ("admins", join_strings(admins.iter())),
+            ("allowed_denoms", msg.allowed_denoms.join(",")),
+        ])))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -42,41 +93,291 @@ pub fn execute(
     match msg {
         ExecuteMsg::BeginRaffleRound {
             expire_type, 
-            minimum_stake, 
             winners_distribution,
-            pay_token
-        } => begin_raffle_round(deps, env, info, expire_type, minimum_stake, winners_distribution, pay_token),
-        ExecuteMsg::JoinRaffleRoundByJuno {
+            prize_tiers,
+            accepted_assets,
+            series,
+            ticket_nft,
+            claim,
+            unawarded,
+            dust
+        } => begin_raffle_round(deps, env, info, expire_type, winners_distribution, prize_tiers, accepted_assets, series, ticket_nft, claim, unawarded, dust),
+        ExecuteMsg::JoinRaffleRound {
             id
-        } => join_raffle_round_by_juno(deps, env, info, id),
-        ExecuteMsg::JoinRaffleRoundByToken (msg) => join_raffle_round_by_token(deps, env, info, msg),
+        } => join_raffle_round_by_native(deps, env, info, id),
+        ExecuteMsg::Receive (msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::SponsorRaffle {id,} => sponsor_raffle_by_native(deps, env, info, id),
         ExecuteMsg::EndRaffleRound {id,} => choose_winners(deps, env, info, id),
+        ExecuteMsg::ClaimPrize {id,} => claim_prize(deps, env, info, id),
+        ExecuteMsg::ExpireClaims {id,} => expire_claims(deps, env, info, id),
+        ExecuteMsg::Redraw {id, rank} => redraw(deps, env, info, id, rank),
+        ExecuteMsg::CancelRaffleRound {id,} => cancel_raffle_round(deps, env, info, id),
+        ExecuteMsg::ArchiveRaffle { id, limit } => archive_raffle(deps, env, id, limit),
+        ExecuteMsg::ContinueSettlement { id, limit } => continue_settlement(deps, id, limit),
+        ExecuteMsg::ReceiveNft (msg) => receive_cw721(deps, env, info, msg),
+        ExecuteMsg::UpdateAllowedDenoms { add, remove } => update_allowed_denoms(deps, info, add, remove),
+        ExecuteMsg::RetryPayout { payout_id, recipient } => retry_payout(deps, info, payout_id, recipient),
+        ExecuteMsg::UpdateJackpot { series, config } => update_jackpot(deps, info, series, config),
+        ExecuteMsg::AddHook { addr } => add_hook(deps, info, addr),
+        ExecuteMsg::RemoveHook { addr } => remove_hook(deps, info, addr),
     }
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        // the hook's own changes are already reverted, the raffle goes on
+        HOOK_REPLY_ID => Ok(Response::default()),
+        id if id >= PAYOUT_REPLY_ID_BASE => payout_reply(deps, id - PAYOUT_REPLY_ID_BASE, msg.result),
+        id => Err(ContractError::Std(StdError::generic_err(format!("Unknown reply id: {}", id)))),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(mut deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    // 0.1 didn't record its version
+    let from = match cw2::CONTRACT.may_load(deps.storage)? {
+        Some(stored) if stored.contract != CONTRACT_NAME => {
+            return Err(ContractError::WrongContract { expected: CONTRACT_NAME.to_string(), found: stored.contract });
+        }
+        Some(stored) => stored.version,
+        None => "0.1.0".to_string(),
+    };
+    if parse_version(&from)? > parse_version(CONTRACT_VERSION)? {
+        return Err(ContractError::CannotDowngrade { from, to: CONTRACT_VERSION.to_string() });
+    }
+
+    let mut migrated = 0u32;
+    if parse_version(&from)? < (0, 2, 0) {
+        migrated = migrate_from_0_1(deps.branch(), msg)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", from)
+        .add_attribute("to_version", CONTRACT_VERSION)
+        .add_attribute("migrated_raffles", migrated.to_string()))
+}
+
+// Parses a major.minor.patch version, ignoring any pre-release or build suffix
+fn parse_version(version: &str) -> Result<(u64, u64, u64), ContractError> {
+    let invalid = || ContractError::InvalidVersion { version: version.to_string() };
+    let core = version.split(['-', '+']).next().unwrap_or_default();
+    let parts = core
+        .split('.')
+        .map(|part| part.parse::<u64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    match parts[..] {
+        [major, minor, patch] => Ok((major, minor, patch)),
+        _ => Err(invalid()),
+    }
+}
+
+// Every 0.1 raffle and each of its tickets is moved in the one migrate transaction, so a deployment can only be
+// migrated if all of that fits in its gas limit. Running out reverts the whole migration, which can be run again.
+fn migrate_from_0_1(deps: DepsMut, msg: MigrateMsg) -> Result<u32, ContractError> {
+    // 0.1 validated admins but kept duplicates
+    let mut admins: Vec<Addr> = vec![];
+    for admin in ADMINS.may_load(deps.storage)?.unwrap_or_default() {
+        let admin = deps.api.addr_validate(admin.as_str())?;
+        if !admins.contains(&admin) {
+            admins.push(admin);
+        }
+    }
+    ADMINS.save(deps.storage, &admins)?;
+
+    if TREASURY.may_load(deps.storage)?.is_none() {
+        let treasury = msg.treasury.ok_or(ContractError::MissingTreasury {})?;
+        TREASURY.save(deps.storage, &deps.api.addr_validate(&treasury)?)?;
+    }
+    if ALLOWED_DENOMS.may_load(deps.storage)?.is_none() {
+        let allowed_denoms = msg.allowed_denoms.unwrap_or_else(|| vec!["ujuno".to_string()]);
+        ALLOWED_DENOMS.save(deps.storage, &allowed_denoms)?;
+    }
+
+    let keys = LEGACY_RAFFLES
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // the 0.1 counter held the last id given out and was missing before the first raffle
+    let mut next_id = COUNTER.may_load(deps.storage)?.map(|counter| counter.counter + 1);
+    // only admins could begin a 0.1 raffle
+    let creator = match admins.first() {
+        Some(admin) => admin.clone(),
+        None => TREASURY.load(deps.storage)?,
+    };
+    for key in keys.iter() {
+        let legacy = LEGACY_RAFFLES.load(deps.storage, key)?;
+        let raffle = migrate_legacy_raffle(deps.storage, legacy, &creator)?;
+
+        LEGACY_RAFFLES.remove(deps.storage, key);
+        RAFFLEMAP.save(deps.storage, raffle.id, &raffle)?;
+        next_id = Some(cmp::max(next_id.unwrap_or(0), raffle.id + 1));
+    }
+    COUNTER.save(deps.storage, &Counter { counter: next_id.unwrap_or(0) })?;
+    update_global_stats(deps.storage, |stats| stats.raffles += keys.len() as u32)?;
+
+    Ok(keys.len() as u32)
+}
+
+fn migrate_legacy_raffle(storage: &mut dyn Storage, legacy: LegacyRaffle, creator: &Addr) -> StdResult<Raffle> {
+    let denom = match legacy.pay_token {
+        Some(token) => Denom::Cw20(Addr::unchecked(token)),
+        None => Denom::Native("ujuno".to_string()),
+    };
+    let mut raffle = Raffle {
+        id: legacy.id,
+        begin_time_stamp: legacy.begin_time_stamp,
+        end_time_stamp: legacy.end_time_stamp,
+        winners_distribution: legacy.winners_distribution.clone(),
+        entrants: 0,
+        tickets: 0,
+        winner_payouts: legacy
+            .winner_payouts
+            .iter()
+            .map(|amount| vec![AssetAmount { denom: denom.clone(), amount: *amount }])
+            .collect(),
+        winners: legacy.winners,
+        active: legacy.active,
+        assets: vec![RaffleAsset { denom: denom.clone(), ticket_price: legacy.minimum_stake, pot: Uint128::zero() }],
+        series: None,
+        creator: creator.clone(),
+        nft_prizes: vec![],
+        cancelled: false,
+        ticket_nft: None,
+        claim: None,
+        pending_claims: vec![],
+        redraws: vec![],
+        jackpot: None,
+        jackpot_contributions: vec![],
+        unawarded: UnawardedPrize::default(),
+        unawarded_amounts: vec![],
+        sponsors: 0,
+        prize_tiers: legacy
+            .winners_distribution
+            .iter()
+            .map(|shares| PrizeTier { winners: 1, prize: TierPrize::Shares { shares: *shares } })
+            .collect(),
+        dust: DustDestination::default(),
+        dust_amounts: vec![],
+        settlement: None,
+        settled_at: None,
+        seed_hash: None,
+        seeded: vec![],
+    };
+
+    // 0.1 didn't record payments, so every ticket counts at the minimum stake it was sure to have paid
+    for player in legacy.players.iter() {
+        add_entry(storage, &mut raffle, &Addr::unchecked(player), 0, legacy.minimum_stake, 1)?;
+    }
+
+    // 0.1 dropped the payouts of the winners it drew, ContinueSettlement sends them
+    if !raffle.winners.is_empty() {
+        raffle.settlement = Some(Settlement { done: 0, total: raffle.winners.len() as u32, refunded_after: None });
+        raffle.settled_at = Some(raffle.end_time_stamp);
+    }
+
+    Ok(raffle)
+}
+
+fn payout_reply(deps: DepsMut, payout_id: u64, result: SubMsgResult) -> Result<Response, ContractError> {
+    let payout = PENDING_PAYOUTS.load(deps.storage, payout_id)?;
+    PENDING_PAYOUTS.remove(deps.storage, payout_id);
+
+    match result {
+        // a prize counts as won once it has been received, even when a retry sent it elsewhere
+        SubMsgResult::Ok(_) => {
+            if let Some(winner) = &payout.winner {
+                credit_win(deps.storage, payout.raffle_id, winner, AssetAmount { denom: payout.denom, amount: payout.amount })?;
+            }
+            Ok(Response::default())
+        }
+        // keep the payout so it can be retried, the rest of the settlement stands
+        SubMsgResult::Err(err) => {
+            FAILED_PAYOUTS.save(deps.storage, payout_id, &payout)?;
+            Ok(Response::new().add_event(Event::new("payout_failed")
+                .add_attribute("payout_id", payout_id.to_string())
+                .add_attribute("raffle_id", payout.raffle_id.to_string())
+                .add_attribute("recipient", payout.recipient)
+                .add_attribute("amount", asset_string(&payout.denom, payout.amount))
+                .add_attribute("error", err)))
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn begin_raffle_round(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     expire_type: u8,
-    minimum_stake: Uint128,
     winners_distribution: Vec<u32>,
-    pay_token: Option<String>
+    prize_tiers: Vec<PrizeTier>,
+    accepted_assets: Vec<AcceptedAsset>,
+    series: Option<String>,
+    ticket_nft: Option<String>,
+    claim: Option<ClaimConfig>,
+    unawarded: UnawardedPrize,
+    dust: DustDestination,
 ) -> Result<Response, ContractError>{
     if !is_admin(deps.as_ref(), info.sender.clone())? {
         return Err(ContractError::Unauthorized {});
     }
 
-    let counter = COUNTER.load(deps.storage);
-    
-    let id;
-    if let Ok(counter) = counter {
-        id = counter.counter + 1;
-    } else {
-        id = 0;
+    if accepted_assets.is_empty() {
+        return Err(ContractError::InvalidAssets {});
     }
-    
-    COUNTER.save(deps.storage, &Counter { counter: id})?;
+
+    let allowed_denoms = ALLOWED_DENOMS.load(deps.storage)?;
+    let mut assets: Vec<RaffleAsset> = vec![];
+    for accepted in accepted_assets {
+        let denom = match accepted.denom {
+            Denom::Native(denom) => Denom::Native(denom),
+            Denom::Cw20(addr) => Denom::Cw20(deps.api.addr_validate(addr.as_str())?),
+        };
+
+        let key = token_key(&denom);
+        if !allowed_denoms.is_empty() && !allowed_denoms.contains(&key) {
+            return Err(ContractError::DenomNotAllowed { denom: key });
+        }
+
+        if accepted.ticket_price.is_zero() || assets.iter().any(|asset| asset.denom == denom) {
+            return Err(ContractError::InvalidAssets {});
+        }
+
+        assets.push(RaffleAsset { denom, ticket_price: accepted.ticket_price, pot: Uint128::zero() });
+    }
+
+    let ticket_nft = ticket_nft.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+
+    // winners_distribution is the short form of one share tier per rank
+    let prize_tiers = match (winners_distribution.is_empty(), prize_tiers.is_empty()) {
+        (false, true) => winners_distribution
+            .iter()
+            .map(|shares| PrizeTier { winners: 1, prize: TierPrize::Shares { shares: *shares } })
+            .collect(),
+        (true, false) => prize_tiers,
+        _ => return Err(ContractError::InvalidPrizeTiers {}),
+    };
+    validate_prize_tiers(&prize_tiers, &assets)?;
+
+    // fixed and percent prizes don't grow, pro rata would leave what nobody won to the treasury
+    let has_shares = prize_tiers.iter().any(|tier| matches!(tier.prize, TierPrize::Shares { .. }));
+    if unawarded == UnawardedPrize::ProRata && !has_shares {
+        return Err(ContractError::ProRataNeedsShares {});
+    }
+
+    if let Some(claim) = &claim {
+        if claim.period == 0 || (claim.unclaimed == UnclaimedPrize::Rollover && series.is_none()) {
+            return Err(ContractError::InvalidClaimConfig {});
+        }
+    }
+
+    let id = COUNTER.load(deps.storage)?.counter;
+    COUNTER.save(deps.storage, &Counter { counter: id + 1 })?;
+    update_global_stats(deps.storage, |stats| stats.raffles += 1)?;
 
     let end_time_stamp = match expire_type {
         0 => env.block.time.plus_seconds(1800),
@@ -86,39 +387,129 @@ pub fn begin_raffle_round(
         _ => return Err(ContractError::WrongExpire {})
     };
 
+    if (unawarded == UnawardedPrize::NextRound || dust == DustDestination::NextRound) && series.is_none() {
+        return Err(ContractError::NextRoundNeedsSeries {});
+    }
+
+    let jackpot = match &series {
+        Some(series) => JACKPOT_CONFIGS.may_load(deps.storage, series)?,
+        None => None,
+    };
+
+    // whatever was carried over for this series seeds the pots
+    let mut seeded = vec![];
+    if let Some(series) = &series {
+        for asset in assets.iter_mut() {
+            let key = token_key(&asset.denom);
+            if let Some(pool) = SERIES_POOLS.may_load(deps.storage, (series, &key))? {
+                asset.pot = pool;
+                seeded.push(AssetAmount { denom: asset.denom.clone(), amount: pool });
+                SERIES_POOLS.remove(deps.storage, (series, &key));
+            }
+        }
+    }
+
     let raffle = Raffle {
         id,
         begin_time_stamp: env.block.time,
         end_time_stamp,
-        minimum_stake,
         winners_distribution,
         winners: Vec::new(),
-        players: Vec::new(),
+        entrants: 0,
+        tickets: 0,
         winner_payouts: Vec::new(),
         active: true,
-        pay_token,
+        assets,
+        series,
+        creator: info.sender,
+        nft_prizes: Vec::new(),
+        cancelled: false,
+        ticket_nft,
+        claim,
+        pending_claims: Vec::new(),
+        redraws: Vec::new(),
+        jackpot,
+        jackpot_contributions: Vec::new(),
+        unawarded,
+        unawarded_amounts: Vec::new(),
+        sponsors: 0,
+        prize_tiers,
+        dust,
+        dust_amounts: Vec::new(),
+        settlement: None,
+        settled_at: None,
+        seed_hash: None,
+        seeded,
     };
 
-    RAFFLEMAP.save(deps.storage, &id.to_string(), &raffle)?;
-
-    Ok(Response::default())
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    let ticket_prices = raffle.assets.iter().map(|asset| asset_string(&asset.denom, asset.ticket_price));
+    let hooks = hook_msgs(deps.as_ref(), RaffleHookMsg::Opened { id })?;
+    Ok(Response::new()
+        .add_attribute("action", "begin_raffle_round")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(Event::new("raffle_opened")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attribute("creator", raffle.creator.to_string())
+            .add_attribute("end_time", raffle.end_time_stamp.seconds().to_string())
+            .add_attribute("ticket_prices", join_strings(ticket_prices))
+            .add_attributes(non_empty_attributes(vec![("series", raffle.series.unwrap_or_default())])))
+        .add_submessages(hooks))
 }
 
 
+fn validate_prize_tiers(prize_tiers: &[PrizeTier], assets: &[RaffleAsset]) -> Result<(), ContractError> {
+    let ranks: u64 = prize_tiers.iter().map(|tier| tier.winners as u64).sum();
+    if ranks > MAX_RANKS {
+        return Err(ContractError::TooManyRanks { max: MAX_RANKS as u32 });
+    }
 
-pub fn join_raffle_round_by_juno(
+    let mut total_bps = 0_u64;
+    // allocate_prizes splits the pot by the share total as a u32
+    let mut total_shares = 0_u32;
+    for tier in prize_tiers {
+        let valid = tier.winners > 0 && match &tier.prize {
+            TierPrize::Fixed { amounts } => !amounts.is_empty() && amounts.iter().all(|amount| {
+                !amount.amount.is_zero() && assets.iter().any(|asset| asset.denom == amount.denom)
+            }),
+            TierPrize::Percent { bps } => {
+                total_bps += *bps as u64;
+                *bps > 0
+            }
+            TierPrize::Shares { shares } => {
+                let tier_shares = shares.checked_mul(tier.winners);
+                match tier_shares.and_then(|tier_shares| total_shares.checked_add(tier_shares)) {
+                    Some(sum) => total_shares = sum,
+                    None => return Err(ContractError::InvalidPrizeTiers {}),
+                }
+                *shares > 0
+            }
+        };
+        if !valid {
+            return Err(ContractError::InvalidPrizeTiers {});
+        }
+    }
+
+    if total_bps > 10_000 {
+        return Err(ContractError::InvalidPrizeTiers {});
+    }
+    Ok(())
+}
+
+pub fn join_raffle_round_by_native(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     id: u32,
 ) -> Result<Response, ContractError> {
-    let mut raffle = RAFFLEMAP.load(deps.storage, &id.to_string())?;
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
 
     if raffle.is_expired(&env.block) {
         return Err(ContractError::RaffleExpired {});
     }
 
-    if raffle.pay_token.is_some() {
+    if !raffle.assets.iter().any(|asset| matches!(asset.denom, Denom::Native(_))) {
         return Err(ContractError::MustPayByToken {});
     }
     
@@ -126,26 +517,70 @@ pub fn join_raffle_round_by_juno(
         return Err(ContractError::WrongPayment {});
     }
 
-    if info.funds[0].denom != "ujuno" {
-        return Err(ContractError::MustPayByJuno {});
-    }
+    let asset_index = match raffle.asset_index(&Denom::Native(info.funds[0].denom.clone())) {
+        Some(asset_index) => asset_index,
+        None => return Err(ContractError::DenomNotAccepted { denom: info.funds[0].denom.clone() }),
+    };
 
-    if info.funds[0].amount < raffle.minimum_stake {
-        return Err(ContractError::NotSufficientFunds {});
-    }
+    let tickets = tickets_for(&raffle, asset_index, info.funds[0].amount)?;
 
     if !can_register(deps.as_ref(), id)? {
         return Err(ContractError::RegistrationsClosed {});
     }
 
-    if is_registered(deps.as_ref(), id, info.sender.to_string())? {
+    if is_registered(deps.as_ref(), id, &info.sender)? {
         return Err(ContractError::AlreadyRegistered {});
     }
 
-    raffle.players.push(info.sender.to_string());
-    RAFFLEMAP.save(deps.storage, &id.to_string(), &raffle)?;
+    let mint_msgs = add_entry(deps.storage, &mut raffle, &info.sender, asset_index, info.funds[0].amount, tickets)?;
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    let hooks = hook_msgs(deps.as_ref(), RaffleHookMsg::Entered { id, player: info.sender.to_string(), tickets })?;
+    Ok(Response::new()
+        .add_attribute("action", "join_raffle_round")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(entered_event(id, info.sender.as_str(), &raffle.assets[asset_index].denom, info.funds[0].amount, tickets))
+        .add_messages(mint_msgs)
+        .add_submessages(hooks))
+}
+
+pub fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::JoinRaffleRound { id } => join_raffle_round_by_token(deps, env, info, wrapper, id),
+        ReceiveMsg::BuyTickets { id } => buy_tickets_by_token(deps, env, info, wrapper, id),
+        ReceiveMsg::SponsorRaffle { id } => sponsor_raffle_by_token(deps, env, info, wrapper, id),
+        ReceiveMsg::FundSeries { series } => fund_series_by_token(deps, info, wrapper, series),
+    }
+}
+
+// Loads an open raffle accepting the cw20 token calling the hook, with the index of that asset
+fn load_token_raffle(
+    deps: Deps,
+    env: &Env,
+    info: &MessageInfo,
+    id: u32,
+) -> Result<(Raffle, usize), ContractError> {
+    let raffle = RAFFLEMAP.load(deps.storage, id)?;
+
+    let asset_index = match raffle.asset_index(&Denom::Cw20(info.sender.clone())) {
+        Some(asset_index) => asset_index,
+        None => return Err(ContractError::WrongPayment {  } ),
+    };
+
+    if raffle.is_expired(&env.block) {
+        return Err(ContractError::RaffleExpired {});
+    }
+
+    if !raffle.active {
+        return Err(ContractError::RegistrationsClosed {});
+    }
 
-    Ok(Response::default())
+    Ok((raffle, asset_index))
 }
 
 pub fn join_raffle_round_by_token(
@@ -153,42 +588,294 @@ pub fn join_raffle_round_by_token(
     env: Env,
     info: MessageInfo,
     wrapper: Cw20ReceiveMsg,
+    id: u32,
 ) -> Result<Response, ContractError>  {
-    let msg: JoinInfo = from_binary(&wrapper.msg)?;
-    let id = msg.id;
+    let (mut raffle, asset_index) = load_token_raffle(deps.as_ref(), &env, &info, id)?;
 
-    let mut raffle = RAFFLEMAP.load(deps.storage, &id.to_string())?;
+    let tickets = tickets_for(&raffle, asset_index, wrapper.amount)?;
 
-    if raffle.pay_token.is_none() {
-        return Err(ContractError::WrongPayment {  } );
+    let player = deps.api.addr_validate(&wrapper.sender)?;
+    if is_registered(deps.as_ref(), id, &player)? {
+        return Err(ContractError::AlreadyRegistered {});
     }
-    let token_addr = raffle.clone().pay_token.unwrap();
 
-    let token_addr = deps.api.addr_validate(token_addr.as_str())?;
-    if token_addr != info.sender {
-        return Err(ContractError::WrongPayment {  } );
+    let mint_msgs = add_entry(deps.storage, &mut raffle, &player, asset_index, wrapper.amount, tickets)?;
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    let event = entered_event(id, &wrapper.sender, &raffle.assets[asset_index].denom, wrapper.amount, tickets);
+    let hooks = hook_msgs(deps.as_ref(), RaffleHookMsg::Entered { id, player: wrapper.sender, tickets })?;
+    Ok(Response::new()
+        .add_attribute("action", "join_raffle_round")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(event)
+        .add_messages(mint_msgs)
+        .add_submessages(hooks))
+}
+
+pub fn buy_tickets_by_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+    id: u32,
+) -> Result<Response, ContractError>  {
+    let (mut raffle, asset_index) = load_token_raffle(deps.as_ref(), &env, &info, id)?;
+
+    let tickets = tickets_for(&raffle, asset_index, wrapper.amount)?;
+    let player = deps.api.addr_validate(&wrapper.sender)?;
+    let mint_msgs = add_entry(deps.storage, &mut raffle, &player, asset_index, wrapper.amount, tickets)?;
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    let event = entered_event(id, &wrapper.sender, &raffle.assets[asset_index].denom, wrapper.amount, tickets);
+    let hooks = hook_msgs(deps.as_ref(), RaffleHookMsg::Entered { id, player: wrapper.sender, tickets })?;
+    Ok(Response::new()
+        .add_attribute("action", "buy_tickets")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(event)
+        .add_messages(mint_msgs)
+        .add_submessages(hooks))
+}
+
+// Tickets amount buys at the asset's ticket price, the rest of it stays in the pot
+fn tickets_for(raffle: &Raffle, asset_index: usize, amount: Uint128) -> Result<u32, ContractError> {
+    let tickets = amount.checked_div(raffle.assets[asset_index].ticket_price).map_err(StdError::from)?;
+    if tickets.is_zero() {
+        return Err(ContractError::NotSufficientFunds {});
+    }
+
+    let max = match raffle.ticket_nft {
+        Some(_) => MAX_NFT_TICKETS_PER_PURCHASE,
+        None => u32::MAX,
+    };
+    u32::try_from(tickets.u128())
+        .ok()
+        .filter(|tickets| *tickets <= max)
+        .ok_or(ContractError::TooManyTickets { max })
+}
+
+fn entered_event(id: u32, player: &str, denom: &Denom, amount: Uint128, tickets: u32) -> Event {
+    Event::new("raffle_entered")
+        .add_attribute("raffle_id", id.to_string())
+        .add_attribute("player", player)
+        .add_attribute("amount", amount)
+        .add_attribute("denom", token_key(denom))
+        .add_attribute("tickets", tickets.to_string())
+}
+
+// Records a paid entry of one or more tickets, returning the mints of ticket NFTs if the raffle has them
+fn add_entry(
+    storage: &mut dyn Storage,
+    raffle: &mut Raffle,
+    player: &Addr,
+    asset_index: usize,
+    amount: Uint128,
+    tickets: u32,
+) -> StdResult<Vec<CosmosMsg>> {
+    // ranges are keyed by their end, an empty one would take over the previous purchase's tickets
+    if tickets == 0 {
+        return Err(StdError::generic_err("An entry needs at least one ticket"));
+    }
+    let end = raffle.tickets.checked_add(tickets).ok_or_else(|| StdError::generic_err("Too many tickets in the raffle"))?;
+    if TICKET_RANGES.has(storage, (raffle.id, end)) {
+        return Err(StdError::generic_err(format!("Ticket range ending at {} already exists", end)));
     }
-       
+
+    let mut mint_msgs = vec![];
+
+    if let Some(ticket_nft) = &raffle.ticket_nft {
+        for ticket_number in raffle.tickets..raffle.tickets + tickets {
+            mint_msgs.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: ticket_nft.to_string(),
+                msg: to_binary(&Cw721BaseExecuteMsg::Mint(MintMsg {
+                    token_id: raffle.ticket_token_id(ticket_number),
+                    owner: player.to_string(),
+                    token_uri: None,
+                    extension: TicketMetadata { raffle_id: raffle.id, ticket_number },
+                }))?,
+                funds: vec![],
+            }));
+        }
+    }
+
+    // every ticket is one more entry in the draw, all bought at once share a range
+    raffle.tickets = end;
+    TICKET_RANGES.save(storage, (raffle.id, raffle.tickets), player)?;
+
+    let existing = entries().may_load(storage, (raffle.id, player))?;
+    let new_entry = existing.is_none();
+    let mut entry = existing.unwrap_or(Entry { raffle_id: raffle.id, player: player.clone(), tickets: 0, paid: vec![] });
+    entry.tickets += tickets;
+    add_amount(&mut entry.paid, &raffle.assets[asset_index].denom, amount);
+    entries().save(storage, (raffle.id, player), &entry)?;
+
+    let denom = &raffle.assets[asset_index].denom;
+    update_player_stats(storage, player, |stats| {
+        stats.entries += new_entry as u32;
+        stats.tickets += tickets;
+        add_amount(&mut stats.staked, denom, amount);
+    })?;
+    update_global_stats(storage, |stats| add_amount(&mut stats.volume, denom, amount))?;
+
+    raffle.entrants += new_entry as u32;
+    raffle.assets[asset_index].pot += amount;
+
+    Ok(mint_msgs)
+}
+
+pub fn sponsor_raffle_by_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+    id: u32,
+) -> Result<Response, ContractError>  {
+    let (mut raffle, asset_index) = load_token_raffle(deps.as_ref(), &env, &info, id)?;
+
+    let sponsor = deps.api.addr_validate(&wrapper.sender)?;
+    let event = add_sponsorship(deps.storage, &mut raffle, &sponsor, asset_index, wrapper.amount)?;
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "sponsor_raffle")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(event))
+}
+
+pub fn sponsor_raffle_by_native(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u32,
+) -> Result<Response, ContractError> {
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
+
     if raffle.is_expired(&env.block) {
         return Err(ContractError::RaffleExpired {});
     }
 
-    if wrapper.amount < raffle.minimum_stake {
-        return Err(ContractError::NotSufficientFunds {});
+    if !raffle.active {
+        return Err(ContractError::RegistrationsClosed {});
     }
 
-    if !can_register(deps.as_ref(), id)? {
-        return Err(ContractError::RegistrationsClosed {});
+    if info.funds.len() != 1 || info.funds[0].amount.is_zero() {
+        return Err(ContractError::WrongPayment {});
     }
 
-    if is_registered(deps.as_ref(), id, wrapper.sender.to_string())? {
-        return Err(ContractError::AlreadyRegistered {});
+    let asset_index = match raffle.asset_index(&Denom::Native(info.funds[0].denom.clone())) {
+        Some(asset_index) => asset_index,
+        None => return Err(ContractError::DenomNotAccepted { denom: info.funds[0].denom.clone() }),
+    };
+
+    let event = add_sponsorship(deps.storage, &mut raffle, &info.sender, asset_index, info.funds[0].amount)?;
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "sponsor_raffle")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(event))
+}
+
+// Adds a sponsor's funds to the pot and records them for a refund on cancel, one Sponsorship per sponsor
+fn add_sponsorship(
+    storage: &mut dyn Storage,
+    raffle: &mut Raffle,
+    sponsor: &Addr,
+    asset_index: usize,
+    amount: Uint128,
+) -> StdResult<Event> {
+    let denom = raffle.assets[asset_index].denom.clone();
+    raffle.assets[asset_index].pot += amount;
+
+    let mut sponsorship = match SPONSORSHIPS.may_load(storage, (raffle.id, sponsor))? {
+        Some(sponsorship) => sponsorship,
+        None => {
+            raffle.sponsors += 1;
+            Sponsorship { raffle_id: raffle.id, sponsor: sponsor.clone(), amounts: vec![] }
+        }
+    };
+    add_amount(&mut sponsorship.amounts, &denom, amount);
+    SPONSORSHIPS.save(storage, (raffle.id, sponsor), &sponsorship)?;
+
+    Ok(Event::new("raffle_sponsored")
+        .add_attribute("raffle_id", raffle.id.to_string())
+        .add_attribute("sponsor", sponsor)
+        .add_attribute("amount", amount)
+        .add_attribute("denom", token_key(&denom)))
+}
+
+pub fn fund_series_by_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+    series: String,
+) -> Result<Response, ContractError>  {
+    let key = token_key(&Denom::Cw20(info.sender));
+    SERIES_POOLS.update(deps.storage, (&series, &key), |pool| -> StdResult<_> {
+        Ok(pool.unwrap_or_default() + wrapper.amount)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "fund_series")
+        .add_attribute("series", series)
+        .add_attribute("funder", wrapper.sender)
+        .add_attribute("amount", wrapper.amount)
+        .add_attribute("denom", key))
+}
+
+pub fn receive_cw721(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    match from_binary(&wrapper.msg)? {
+        ReceiveNftMsg::AddPrize { id, rank } => add_nft_prize(deps, env, info, wrapper, id, rank),
     }
+}
+
+pub fn add_nft_prize(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+    id: u32,
+    rank: u32,
+) -> Result<Response, ContractError> {
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
 
-    raffle.players.push(wrapper.sender.to_string());
-    RAFFLEMAP.save(deps.storage, &msg.id.to_string(), &raffle)?;
+    let depositor = deps.api.addr_validate(&wrapper.sender)?;
+    if depositor != raffle.creator {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if !raffle.active {
+        return Err(ContractError::RaffleClosed {});
+    }
 
-    Ok(Response::default())
+    if raffle.is_expired(&env.block) {
+        return Err(ContractError::RaffleExpired {});
+    }
+
+    if rank as usize >= raffle.rank_count() {
+        return Err(ContractError::InvalidPrizeRank { rank });
+    }
+
+    let res = Response::new()
+        .add_attribute("action", "add_nft_prize")
+        .add_attribute("raffle_id", id.to_string())
+        .add_attribute("nft_contract", info.sender.to_string())
+        .add_attribute("token_id", wrapper.token_id.clone())
+        .add_attribute("rank", rank.to_string());
+
+    raffle.nft_prizes.push(NftPrize {
+        contract: info.sender,
+        token_id: wrapper.token_id,
+        rank,
+        depositor,
+    });
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    Ok(res)
 }
 
 pub fn choose_winners(
@@ -201,7 +888,11 @@ pub fn choose_winners(
         return Err(ContractError::Unauthorized {});
     }
 
-    let raffle = RAFFLEMAP.load(deps.storage, &id.to_string())?;
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
+
+    if !raffle.active {
+        return Err(ContractError::RaffleClosed {});
+    }
 
     if !raffle.is_expired(&env.block) {
         return Err(ContractError::RaffleNotEnded {});
@@ -211,64 +902,1050 @@ pub fn choose_winners(
     let random_seed = new_entropy(&info, &env, prng_seed.as_ref(), prng_seed.as_ref());
     let mut rng = ChaChaRng::from_seed(random_seed);
 
-    let nb_players = raffle.players.len() as u32;
-    let total_shares = raffle.clone().winners_distribution.iter().sum::<u32>();
-
-    let total_deposit = query_total_deposit(deps.as_ref(), env)?;
+    let nb_players = raffle.tickets;
+    let claim_deadline = raffle.claim.as_ref().map(|claim| env.block.time.plus_seconds(claim.period));
+    let rank_tiers: Vec<PrizeTier> = raffle.rank_tiers().into_iter().cloned().collect();
+    let nb_winners = cmp::min(rank_tiers.len(), nb_players as usize);
+    // pro rata leaves the prizes of ranks nobody won in the pot for the others
+    let counted_ranks = match raffle.unawarded {
+        UnawardedPrize::ProRata => nb_winners,
+        _ => rank_tiers.len(),
+    };
 
-    let res = Response::new();
+    let mut res = Response::new();
+    let mut winner_events = vec![];
     let mut winner_addresses = vec![];
     let mut payouts = vec![];
 
-    for counter in 0..cmp::min(raffle.winners_distribution.len(), nb_players as usize) {
-        let id_winner = (rng.next_u32() % nb_players) as usize;
+    // a share of each pot feeds the series jackpot, which the first winner may take as well
+    let mut distributable: Vec<Uint128> = raffle.assets.iter().map(|asset| asset.pot).collect();
+    let mut jackpot_won = vec![];
+    if let (Some(jackpot), Some(series), true) = (&raffle.jackpot, &raffle.series, nb_players > 0) {
+        let mut pool = JACKPOTS.may_load(deps.storage, series)?.unwrap_or_default();
+        for (index, asset) in raffle.assets.iter().enumerate() {
+            let share = asset.pot.multiply_ratio(jackpot.share_bps, 10_000_u32);
+            if share.is_zero() {
+                continue;
+            }
+            distributable[index] -= share;
+            add_amount(&mut pool, &asset.denom, share);
+            raffle.jackpot_contributions.push(AssetAmount { denom: asset.denom.clone(), amount: share });
+        }
+
+        if rng.next_u32() % jackpot.odds == 0 {
+            jackpot_won = pool;
+            JACKPOTS.remove(deps.storage, series);
+        } else {
+            JACKPOTS.save(deps.storage, series, &pool)?;
+        }
+    }
+
+    // each asset's pot is shared out separately and paid in that asset
+    let mut allocations = vec![];
+    let mut unawarded = vec![];
+    let mut dust = vec![];
+    for (index, asset) in raffle.assets.iter().enumerate().filter(|(_, asset)| !asset.pot.is_zero()) {
+        let (mut amounts, unallocated, remainder) = allocate_prizes(&rank_tiers, counted_ranks, &asset.denom, distributable[index]);
+        let mut amount = amounts[nb_winners..].iter().fold(unallocated, |sum, amount| sum + *amount);
+        if !remainder.is_zero() {
+            let remainder = AssetAmount { denom: asset.denom.clone(), amount: remainder };
+            match raffle.dust {
+                // without a first winner it goes with the unawarded prizes and isn't dust
+                DustDestination::FirstWinner if nb_winners == 0 => amount += remainder.amount,
+                DustDestination::FirstWinner => {
+                    amounts[0] += remainder.amount;
+                    dust.push(remainder);
+                }
+                _ => dust.push(remainder),
+            }
+        }
+        if !amount.is_zero() {
+            unawarded.push(AssetAmount { denom: asset.denom.clone(), amount });
+        }
+        allocations.push((asset.denom.clone(), amounts));
+    }
 
-        let winner_address = raffle.players[id_winner].to_owned();
+    for counter in 0..nb_winners {
+        let winner_address = draw_winner(deps.as_ref(), &raffle, &mut rng)?;
 
         winner_addresses.push(winner_address.clone());
 
-        let reward_per_share = total_deposit.checked_div(Uint128::from(total_shares)).unwrap();
-        println!("total_shares: {}, reward_per_share: {}", total_shares, reward_per_share);
+        let mut rewards: Vec<AssetAmount> = allocations
+            .iter()
+            .map(|(denom, amounts)| AssetAmount { denom: denom.clone(), amount: amounts[counter] })
+            .collect();
+        if counter == 0 {
+            for amount in jackpot_won.iter() {
+                add_amount(&mut rewards, &amount.denom, amount.amount);
+            }
+        }
+
+        if let Some(deadline) = claim_deadline {
+            raffle.pending_claims.push(PendingClaim { rank: counter as u32, deadline });
+        }
 
-        let reward = reward_per_share.checked_mul(Uint128::from(raffle.winners_distribution[counter])).unwrap();
-        println!("reward: {}", reward);
+        winner_events.push(Event::new("raffle_winner")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attribute("rank", counter.to_string())
+            .add_attribute("winner", winner_address)
+            .add_attributes(non_empty_attributes(vec![
+                ("payout", join_strings(rewards.iter().map(|reward| asset_string(&reward.denom, reward.amount)))),
+                ("claim_deadline", claim_deadline.map(|deadline| deadline.seconds().to_string()).unwrap_or_default()),
+            ])));
       
-        payouts.push(reward);
-
-        if raffle.clone().pay_token.is_none() {
-            res.clone().add_message(BankMsg::Send { 
-                to_address: winner_address, 
-                amount: vec![Coin {
-                    denom: String::from("ujuno"),
-                    amount: reward,
-                }]
-            });
+        payouts.push(rewards);
+    }
+    raffle.winners = winner_addresses;
+    raffle.winner_payouts = payouts;
+
+    // in claim mode the rewards stay in the contract until ClaimPrize, otherwise the first batch is paid now
+    if raffle.claim.is_none() && nb_winners > 0 {
+        raffle.settlement = Some(Settlement { done: 0, total: nb_winners as u32, refunded_after: None });
+        let (msgs, _) = settle_batch(deps.storage, &mut raffle, SETTLEMENT_LIMIT)?;
+        res = res.add_submessages(msgs);
+    }
+
+    // NFTs of ranks nobody won go back to the depositor, won ones wait for the claim in claim mode
+    for prize in raffle.nft_prizes.iter() {
+        let recipient = match raffle.winners.get(prize.rank as usize) {
+            Some(_) if raffle.claim.is_some() => continue,
+            Some(winner) => winner.clone(),
+            None => prize.depositor.to_string(),
+        };
+        res = res.add_message(transfer_nft_msg(prize, &recipient)?);
+    }
+
+    // prizes of the ranks nobody won, and the dust if it doesn't go to the first winner
+    let treasury = TREASURY.load(deps.storage)?;
+    let next_round = raffle.unawarded == UnawardedPrize::NextRound;
+    res = res.add_submessages(leftover_msgs(deps.storage, &raffle, next_round, &unawarded, &treasury)?);
+    if raffle.dust != DustDestination::FirstWinner {
+        let next_round = raffle.dust == DustDestination::NextRound;
+        res = res.add_submessages(leftover_msgs(deps.storage, &raffle, next_round, &dust, &treasury)?);
+    }
+    raffle.unawarded_amounts = unawarded;
+    raffle.dust_amounts = dust;
+
+    if !jackpot_won.is_empty() {
+        let series = raffle.series.clone().unwrap_or_default();
+        let hit = JackpotHit {
+            raffle_id: id,
+            winner: raffle.winners[0].clone(),
+            amounts: jackpot_won,
+            time: env.block.time,
+        };
+        JACKPOT_HITS.save(deps.storage, (&series, id), &hit)?;
+        res = res.add_event(Event::new("jackpot_won")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attribute("series", series)
+            .add_attribute("winner", hit.winner)
+            .add_attribute("amount", join_strings(hit.amounts.iter().map(|amount| asset_string(&amount.denom, amount.amount)))));
+    }
+
+    raffle.active = false;
+    raffle.settled_at = Some(env.block.time);
+    raffle.seed_hash = Some(hex_string(&sha_256(&random_seed)));
+
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    let hooks = hook_msgs(deps.as_ref(), RaffleHookMsg::Settled { id, winners: raffle.winners.clone() })?;
+    Ok(res
+        .add_attribute("action", "end_raffle_round")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(Event::new("raffle_settled")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attribute("tickets", raffle.tickets.to_string())
+            .add_attribute("pot", join_strings(raffle.assets.iter().map(|asset| asset_string(&asset.denom, asset.pot))))
+            .add_attributes(non_empty_attributes(vec![
+                ("winners", raffle.winners.join(",")),
+                ("unawarded", join_strings(raffle.unawarded_amounts.iter().map(|amount| asset_string(&amount.denom, amount.amount)))),
+                ("dust", join_strings(raffle.dust_amounts.iter().map(|amount| asset_string(&amount.denom, amount.amount)))),
+                ("jackpot_contribution", join_strings(raffle.jackpot_contributions.iter().map(|amount| asset_string(&amount.denom, amount.amount)))),
+                ("remaining", raffle.settlement.as_ref().map(|settlement| settlement.remaining().to_string()).unwrap_or_default()),
+            ]))
+            .add_attribute("seed_hash", raffle.seed_hash.unwrap_or_default()))
+        .add_events(winner_events)
+        .add_submessages(hooks))
+}
+
+// Sends amounts left over from a draw into the series pool when next_round is set, to the treasury otherwise
+fn leftover_msgs(
+    storage: &mut dyn Storage,
+    raffle: &Raffle,
+    next_round: bool,
+    amounts: &[AssetAmount],
+    treasury: &Addr,
+) -> StdResult<Vec<SubMsg>> {
+    let mut msgs = vec![];
+    for amount in amounts.iter() {
+        match (&raffle.series, next_round) {
+            (Some(series), true) => {
+                SERIES_POOLS.update(storage, (series, &token_key(&amount.denom)), |pool| -> StdResult<_> {
+                    Ok(pool.unwrap_or_default() + amount.amount)
+                })?;
+            }
+            _ => msgs.push(payout_submsg(storage, raffle.id, treasury.as_str(), &amount.denom, amount.amount, None)?),
+        }
+    }
+    Ok(msgs)
+}
+
+// Splits what can be distributed of one asset between the ranks. Only the first counted_ranks ranks take part,
+// the others get nothing. Returns the amount of every rank, what no share rank was there to take and the
+// remainder of dividing into shares, which all add up to distributable.
+fn allocate_prizes(rank_tiers: &[PrizeTier], counted_ranks: usize, denom: &Denom, distributable: Uint128) -> (Vec<Uint128>, Uint128, Uint128) {
+    let mut amounts = vec![Uint128::zero(); rank_tiers.len()];
+    let mut remaining = distributable;
+
+    for (rank, tier) in rank_tiers.iter().enumerate().take(counted_ranks) {
+        if let TierPrize::Fixed { amounts: fixed } = &tier.prize {
+            let amount = fixed.iter().filter(|fixed| fixed.denom == *denom).map(|fixed| fixed.amount).sum::<Uint128>();
+            amounts[rank] = cmp::min(amount, remaining);
+            remaining -= amounts[rank];
+        }
+    }
+
+    // a percent tier's share is split evenly between its ranks, what doesn't divide is dust
+    let after_fixed = remaining;
+    let mut percent_dust = Uint128::zero();
+    let mut first_rank = 0;
+    while first_rank < rank_tiers.len() {
+        let tier = &rank_tiers[first_rank];
+        if let TierPrize::Percent { bps } = tier.prize {
+            let tier_amount = after_fixed.multiply_ratio(bps, 10_000_u32);
+            let rank_amount = tier_amount.multiply_ratio(1_u32, tier.winners);
+            for amount in amounts.iter_mut().take(counted_ranks).skip(first_rank).take(tier.winners as usize) {
+                *amount = rank_amount;
+                remaining -= rank_amount;
+            }
+            percent_dust += tier_amount - rank_amount * Uint128::from(tier.winners);
+        }
+        first_rank += tier.winners as usize;
+    }
+
+    let total_shares: u32 = rank_tiers.iter().take(counted_ranks).map(|tier| match tier.prize {
+        TierPrize::Shares { shares } => shares,
+        _ => 0,
+    }).sum();
+    if total_shares == 0 {
+        return (amounts, remaining - percent_dust, percent_dust);
+    }
+
+    let per_share = remaining / Uint128::from(total_shares);
+    for (rank, tier) in rank_tiers.iter().enumerate().take(counted_ranks) {
+        if let TierPrize::Shares { shares } = tier.prize {
+            amounts[rank] = per_share * Uint128::from(shares);
+        }
+    }
+    (amounts, Uint128::zero(), remaining - per_share * Uint128::from(total_shares))
+}
+
+// Picks a ticket at random and returns its holder
+fn draw_winner(deps: Deps, raffle: &Raffle, rng: &mut ChaChaRng) -> StdResult<String> {
+    let ticket = rng.next_u32() % raffle.tickets;
+    ticket_holder(deps, raffle, ticket)
+}
+
+fn ticket_holder(deps: Deps, raffle: &Raffle, ticket: u32) -> StdResult<String> {
+    // a ticket NFT may have changed hands since it was bought; a burned one
+    // stays with whoever bought it so it can't block the draw
+    if let Some(ticket_nft) = &raffle.ticket_nft {
+        let owner: StdResult<OwnerOfResponse> = deps.querier.query_wasm_smart(
+            ticket_nft,
+            &Cw721QueryMsg::OwnerOf { token_id: raffle.ticket_token_id(ticket), include_expired: None },
+        );
+        if let Ok(owner) = owner {
+            return Ok(owner.owner);
+        }
+    }
+    let (_, holder) = TICKET_RANGES
+        .prefix(raffle.id)
+        .range(deps.storage, Some(Bound::exclusive(ticket)), None, Order::Ascending)
+        .next()
+        .ok_or_else(|| StdError::not_found("ticket"))??;
+    Ok(holder.to_string())
+}
+
+pub fn claim_prize(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u32,
+) -> Result<Response, ContractError> {
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
+    if raffle.settlement.is_some() {
+        return Err(ContractError::SettlementInProgress {});
+    }
+
+    let (won, others): (Vec<PendingClaim>, Vec<PendingClaim>) = raffle
+        .pending_claims
+        .iter()
+        .cloned()
+        .partition(|claim| raffle.winners[claim.rank as usize] == info.sender);
+    if won.is_empty() {
+        return Err(ContractError::NothingToClaim {});
+    }
+
+    // prizes past their deadline are left to ExpireClaims
+    let (claimable, expired): (Vec<PendingClaim>, Vec<PendingClaim>) =
+        won.into_iter().partition(|claim| env.block.time < claim.deadline);
+    if claimable.is_empty() {
+        return Err(ContractError::ClaimExpired {});
+    }
+
+    let mut res = Response::new();
+    let mut payout = vec![];
+    for claim in claimable.iter() {
+        count_win(deps.storage, info.sender.as_str())?;
+        for reward in raffle.winner_payouts[claim.rank as usize].iter().filter(|reward| !reward.amount.is_zero()) {
+            let winner = Some(info.sender.as_str());
+            res = res.add_submessage(payout_submsg(deps.storage, id, info.sender.as_str(), &reward.denom, reward.amount, winner)?);
+            payout.push(asset_string(&reward.denom, reward.amount));
+        }
+        for prize in raffle.nft_prizes.iter().filter(|prize| prize.rank == claim.rank) {
+            res = res.add_message(transfer_nft_msg(prize, info.sender.as_str())?);
+        }
+    }
+
+    raffle.pending_claims = others.into_iter().chain(expired).collect();
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    Ok(res
+        .add_attribute("action", "claim_prize")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(Event::new("prize_claimed")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attribute("winner", info.sender)
+            .add_attribute("ranks", join_strings(claimable.iter().map(|claim| claim.rank)))
+            .add_attributes(non_empty_attributes(vec![("payout", payout.join(","))]))))
+}
+
+pub fn expire_claims(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u32,
+) -> Result<Response, ContractError> {
+    if !is_admin(deps.as_ref(), info.sender.clone())? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
+    if raffle.settlement.is_some() {
+        return Err(ContractError::SettlementInProgress {});
+    }
+    let config = match &raffle.claim {
+        Some(config) => config.clone(),
+        None => return Err(ContractError::NoExpiredClaims {}),
+    };
+
+    let (expired, pending): (Vec<PendingClaim>, Vec<PendingClaim>) = raffle
+        .pending_claims
+        .iter()
+        .cloned()
+        .partition(|claim| claim.deadline <= env.block.time);
+    if expired.is_empty() {
+        return Err(ContractError::NoExpiredClaims {});
+    }
+    raffle.pending_claims = pending;
+
+    let treasury = TREASURY.load(deps.storage)?;
+
+    let mut res = Response::new();
+    let mut events = vec![];
+    for claim in expired {
+        let rank = claim.rank as usize;
+        let mut event = Event::new("prize_expired")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attribute("rank", rank.to_string())
+            .add_attribute("winner", raffle.winners[rank].clone());
+
+        match config.unclaimed {
+            UnclaimedPrize::Treasury => {
+                res = res.add_submessages(unclaimed_to_treasury(deps.storage, &raffle, claim.rank, &treasury)?);
+                event = event.add_attribute("outcome", "treasury");
+            }
+            UnclaimedPrize::Rollover => {
+                let series = raffle.series.clone().unwrap_or_default();
+                for reward in raffle.winner_payouts[rank].iter().filter(|reward| !reward.amount.is_zero()) {
+                    SERIES_POOLS.update(deps.storage, (&series, &token_key(&reward.denom)), |pool| -> StdResult<_> {
+                        Ok(pool.unwrap_or_default() + reward.amount)
+                    })?;
+                }
+                // NFTs can't be pooled, they go back to the depositor
+                for prize in raffle.nft_prizes.iter().filter(|prize| prize.rank == claim.rank) {
+                    res = res.add_message(transfer_nft_msg(prize, prize.depositor.as_str())?);
+                }
+                event = event.add_attribute("outcome", "rollover");
+            }
+            UnclaimedPrize::Redraw => {
+                let (redrawn, msgs) = redraw_rank(deps.branch(), &env, &info, &mut raffle, claim.rank, &treasury)?;
+                res = res.add_submessages(msgs);
+                event = event.add_attribute("outcome", "redraw");
+                events.push(event);
+                event = redrawn;
+            }
+        }
+        events.push(event);
+    }
+
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    Ok(res
+        .add_attribute("action", "expire_claims")
+        .add_attribute("raffle_id", id.to_string())
+        .add_events(events))
+}
+
+pub fn redraw(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u32,
+    rank: u32,
+) -> Result<Response, ContractError> {
+    if !is_admin(deps.as_ref(), info.sender.clone())? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
+    if raffle.settlement.is_some() {
+        return Err(ContractError::SettlementInProgress {});
+    }
+    if raffle.claim.as_ref().map(|claim| &claim.unclaimed) != Some(&UnclaimedPrize::Redraw) {
+        return Err(ContractError::RedrawNotAllowed {});
+    }
+
+    let position = raffle
+        .pending_claims
+        .iter()
+        .position(|claim| claim.rank == rank && claim.deadline <= env.block.time);
+    match position {
+        Some(position) => raffle.pending_claims.remove(position),
+        None => return Err(ContractError::ClaimNotExpired { rank }),
+    };
+
+    let treasury = TREASURY.load(deps.storage)?;
+    let (event, msgs) = redraw_rank(deps.branch(), &env, &info, &mut raffle, rank, &treasury)?;
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "redraw")
+        .add_attribute("raffle_id", id.to_string())
+        .add_attribute("rank", rank.to_string())
+        .add_event(event)
+        .add_submessages(msgs))
+}
+
+// The winner of rank forfeits and a new one is drawn with fresh entropy among the tickets of players who haven't forfeited.
+// With no such ticket left the prize goes to the treasury.
+fn redraw_rank(
+    deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    raffle: &mut Raffle,
+    rank: u32,
+    treasury: &Addr,
+) -> Result<(Event, Vec<SubMsg>), ContractError> {
+    let forfeited = raffle.winners[rank as usize].clone();
+
+    let mut excluded: Vec<String> = raffle.redraws.iter().map(|redraw| redraw.forfeited.clone()).collect();
+    excluded.push(forfeited.clone());
+
+    let prng_seed: Vec<u8> = sha_256(base64::encode("entropy").as_bytes()).to_vec();
+    let entropy = [raffle.id.to_be_bytes(), rank.to_be_bytes(), (raffle.redraws.len() as u32).to_be_bytes()].concat();
+    let random_seed = new_entropy(info, env, prng_seed.as_ref(), &entropy);
+    let mut rng = ChaChaRng::from_seed(random_seed);
+
+    // drawing from all tickets keeps the cost bounded however many were sold
+    let mut winner = None;
+    for _ in 0..REDRAW_ATTEMPTS {
+        let holder = draw_winner(deps.as_ref(), raffle, &mut rng)?;
+        if !excluded.contains(&holder) {
+            winner = Some(holder);
+            break;
+        }
+    }
+    // when excluded players hold most tickets, the eligible ones are found among the entries
+    if winner.is_none() {
+        winner = draw_eligible_entry(deps.as_ref(), raffle, &excluded, &mut rng)?;
+    }
+
+    let mut msgs = vec![];
+    let mut deadline = None;
+    let winner = if let Some(winner) = winner {
+        let claim_deadline = env.block.time.plus_seconds(raffle.claim.as_ref().map(|claim| claim.period).unwrap_or_default());
+        raffle.winners[rank as usize] = winner.clone();
+        raffle.pending_claims.push(PendingClaim { rank, deadline: claim_deadline });
+        deadline = Some(claim_deadline);
+        Some(winner)
+    } else {
+        msgs = unclaimed_to_treasury(deps.storage, raffle, rank, treasury)?;
+        None
+    };
+
+    let seed_hash = hex_string(&sha_256(&random_seed));
+    raffle.redraws.push(RedrawRecord {
+        rank,
+        forfeited: forfeited.clone(),
+        winner: winner.clone(),
+        time: env.block.time,
+        seed_hash: seed_hash.clone(),
+    });
+
+    let event = Event::new("prize_redrawn")
+        .add_attribute("raffle_id", raffle.id.to_string())
+        .add_attribute("rank", rank.to_string())
+        .add_attribute("forfeited", forfeited)
+        .add_attributes(non_empty_attributes(vec![
+            ("winner", winner.unwrap_or_default()),
+            ("claim_deadline", deadline.map(|deadline| deadline.seconds().to_string()).unwrap_or_default()),
+        ]))
+        .add_attribute("seed_hash", seed_hash);
+    Ok((event, msgs))
+}
+
+// Picks a buyer weighted by their tickets among the first entries of players not excluded, None if there is none
+fn draw_eligible_entry(deps: Deps, raffle: &Raffle, excluded: &[String], rng: &mut ChaChaRng) -> StdResult<Option<String>> {
+    let eligible = entries()
+        .prefix(raffle.id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(REDRAW_SCAN_LIMIT)
+        .filter(|item| item.as_ref().map_or(true, |(player, _)| !excluded.iter().any(|excluded| *excluded == *player)))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let total: u64 = eligible.iter().map(|(_, entry)| entry.tickets as u64).sum();
+    if total == 0 {
+        return Ok(None);
+    }
+    let mut ticket = rng.next_u64() % total;
+    for (player, entry) in eligible {
+        if ticket < entry.tickets as u64 {
+            return Ok(Some(player.to_string()));
+        }
+        ticket -= entry.tickets as u64;
+    }
+    Ok(None)
+}
+
+// Sends the winnings of an unclaimed rank to the treasury and its NFTs back to their depositor
+fn unclaimed_to_treasury(storage: &mut dyn Storage, raffle: &Raffle, rank: u32, treasury: &Addr) -> StdResult<Vec<SubMsg>> {
+    let mut msgs = vec![];
+    for reward in raffle.winner_payouts[rank as usize].iter().filter(|reward| !reward.amount.is_zero()) {
+        msgs.push(payout_submsg(storage, raffle.id, treasury.as_str(), &reward.denom, reward.amount, None)?);
+    }
+    for prize in raffle.nft_prizes.iter().filter(|prize| prize.rank == rank) {
+        msgs.push(SubMsg::new(transfer_nft_msg(prize, prize.depositor.as_str())?));
+    }
+    Ok(msgs)
+}
+
+pub fn cancel_raffle_round(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u32,
+) -> Result<Response, ContractError> {
+    if !is_admin(deps.as_ref(), info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
+
+    if !raffle.active {
+        return Err(ContractError::RaffleClosed {});
+    }
+
+    raffle.active = false;
+    raffle.cancelled = true;
+    raffle.settled_at = Some(env.block.time);
+
+    // what the series carried over waits in its pool for the next raffle
+    if let Some(series) = &raffle.series {
+        for amount in raffle.seeded.iter() {
+            SERIES_POOLS.update(deps.storage, (series, &token_key(&amount.denom)), |pool| -> StdResult<_> {
+                Ok(pool.unwrap_or_default() + amount.amount)
+            })?;
+        }
+    }
+
+    // refunds go to whoever paid, even if ticket NFTs were traded since, the first batch now
+    let mut res = Response::new();
+    let total = raffle.entrants + raffle.sponsors;
+    if total > 0 {
+        raffle.settlement = Some(Settlement { done: 0, total, refunded_after: None });
+    }
+    let (msgs, refunds) = settle_batch(deps.storage, &mut raffle, SETTLEMENT_LIMIT)?;
+    res = res.add_submessages(msgs);
+    for prize in raffle.nft_prizes.iter() {
+        res = res.add_message(transfer_nft_msg(prize, prize.depositor.as_str())?);
+    }
+
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    let hooks = hook_msgs(deps.as_ref(), RaffleHookMsg::Cancelled { id })?;
+    Ok(res
+        .add_attribute("action", "cancel_raffle_round")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(Event::new("raffle_cancelled")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attributes(non_empty_attributes(vec![
+                ("refunds", refunds.join(",")),
+                ("returned_to_series", join_strings(raffle.seeded.iter().map(|amount| asset_string(&amount.denom, amount.amount)))),
+                ("remaining", raffle.settlement.as_ref().map(|settlement| settlement.remaining().to_string()).unwrap_or_default()),
+            ])))
+        .add_submessages(hooks))
+}
+
+pub fn archive_raffle(
+    deps: DepsMut,
+    env: Env,
+    id: u32,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let mut summary = match ARCHIVE.may_load(deps.storage, id)? {
+        Some(summary) if summary.pruning => summary,
+        Some(_) => return Err(ContractError::AlreadyArchived {}),
+        None => {
+            let raffle = RAFFLEMAP.load(deps.storage, id)?;
+            // raffles settled before settled_at was recorded count from their end
+            let settled_at = raffle.settled_at.unwrap_or(raffle.end_time_stamp);
+            if raffle.active
+                || raffle.settlement.is_some()
+                || !raffle.pending_claims.is_empty()
+                || env.block.time < settled_at.plus_seconds(ARCHIVE_RETENTION)
+            {
+                return Err(ContractError::NotArchivable {});
+            }
+
+            RAFFLEMAP.remove(deps.storage, id);
+            RaffleSummary {
+                id,
+                begin_time_stamp: raffle.begin_time_stamp,
+                end_time_stamp: raffle.end_time_stamp,
+                settled_at,
+                cancelled: raffle.cancelled,
+                series: raffle.series,
+                entrants: raffle.entrants,
+                tickets: raffle.tickets,
+                pot: raffle.assets.iter().map(|asset| AssetAmount { denom: asset.denom.clone(), amount: asset.pot }).collect(),
+                winners: raffle.winners,
+                winner_payouts: raffle.winner_payouts,
+                seed_hash: raffle.seed_hash,
+                pruning: true,
+            }
+        }
+    };
+
+    let limit = limit.unwrap_or(SETTLEMENT_LIMIT).clamp(1, MAX_SETTLEMENT_LIMIT) as usize;
+    // the entries live on in their players' history
+    let players = entries()
+        .prefix(id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (player, entry) in players.iter() {
+        let archived = ArchivedEntry {
+            tickets: entry.tickets,
+            paid: entry.paid.clone(),
+            won: won_by(player, &summary.winners, &summary.winner_payouts),
+        };
+        PLAYER_ARCHIVE.save(deps.storage, (player, id), &archived)?;
+        entries().remove(deps.storage, (id, player))?;
+    }
+    let ranges = TICKET_RANGES
+        .prefix(id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    for end in ranges.iter() {
+        TICKET_RANGES.remove(deps.storage, (id, *end));
+    }
+    let sponsors = SPONSORSHIPS
+        .prefix(id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+    for sponsor in sponsors.iter() {
+        SPONSORSHIPS.remove(deps.storage, (id, sponsor));
+    }
+    summary.pruning = players.len() == limit || ranges.len() == limit || sponsors.len() == limit;
+    ARCHIVE.save(deps.storage, id, &summary)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "archive_raffle")
+        .add_attribute("raffle_id", id.to_string())
+        .add_attribute("pruned_entries", players.len().to_string())
+        .add_attribute("pruning", summary.pruning.to_string()))
+}
+
+pub fn continue_settlement(
+    deps: DepsMut,
+    id: u32,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let mut raffle = RAFFLEMAP.load(deps.storage, id)?;
+    if raffle.settlement.is_none() {
+        return Err(ContractError::NothingToSettle {});
+    }
+
+    let limit = limit.unwrap_or(SETTLEMENT_LIMIT).clamp(1, MAX_SETTLEMENT_LIMIT);
+    let (msgs, payouts) = settle_batch(deps.storage, &mut raffle, limit)?;
+    RAFFLEMAP.save(deps.storage, id, &raffle)?;
+
+    let remaining = raffle.settlement.as_ref().map(|settlement| settlement.remaining()).unwrap_or_default();
+    Ok(Response::new()
+        .add_attribute("action", "continue_settlement")
+        .add_attribute("raffle_id", id.to_string())
+        .add_event(Event::new("settlement_continued")
+            .add_attribute("raffle_id", id.to_string())
+            .add_attributes(non_empty_attributes(vec![("payouts", payouts.join(","))]))
+            .add_attribute("remaining", remaining.to_string()))
+        .add_submessages(msgs))
+}
+
+// Pays the next limit recipients of the raffle's settlement: its winners by rank, or when cancelled its players
+// then its sponsors. Returns the payouts with recipient:amount pairs describing them, and clears the settlement
+// once everyone is paid.
+fn settle_batch(storage: &mut dyn Storage, raffle: &mut Raffle, limit: u32) -> StdResult<(Vec<SubMsg>, Vec<String>)> {
+    let mut settlement = match raffle.settlement.take() {
+        Some(settlement) => settlement,
+        None => return Ok((vec![], vec![])),
+    };
+    let end = cmp::min(settlement.done + limit, settlement.total);
+
+    let mut payouts: Vec<(String, AssetAmount)> = vec![];
+    if raffle.cancelled {
+        let players = cmp::min(end, raffle.entrants).saturating_sub(settlement.done);
+        let start = settlement.refunded_after.as_ref().map(Bound::exclusive);
+        let refunded = entries()
+            .prefix(raffle.id)
+            .range(storage, start, None, Order::Ascending)
+            .take(players as usize)
+            .map(|item| item.map(|(_, entry)| entry))
+            .collect::<StdResult<Vec<_>>>()?;
+        for entry in refunded {
+            update_player_stats(storage, &entry.player, |stats| {
+                stats.entries = stats.entries.saturating_sub(1);
+                stats.tickets = stats.tickets.saturating_sub(entry.tickets);
+                for amount in entry.paid.iter() {
+                    sub_amount(&mut stats.staked, &amount.denom, amount.amount);
+                }
+            })?;
+            update_global_stats(storage, |stats| {
+                for amount in entry.paid.iter() {
+                    sub_amount(&mut stats.volume, &amount.denom, amount.amount);
+                }
+            })?;
+            for amount in entry.paid {
+                payouts.push((entry.player.to_string(), amount));
+            }
+            settlement.refunded_after = Some(entry.player);
+            settlement.done += 1;
+        }
+        // sponsors are gone through by address the same way, once every player is refunded
+        let sponsors = end.saturating_sub(cmp::max(settlement.done, raffle.entrants));
+        let start = if settlement.done > raffle.entrants {
+            settlement.refunded_after.as_ref().map(Bound::exclusive)
         } else {
-            res.clone().add_message(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: raffle.clone().pay_token.unwrap(),
-                msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: winner_address, amount: reward }).unwrap(),
-                funds: vec![]
-            }));
+            None
+        };
+        let refunded = SPONSORSHIPS
+            .prefix(raffle.id)
+            .range(storage, start, None, Order::Ascending)
+            .take(sponsors as usize)
+            .map(|item| item.map(|(_, sponsorship)| sponsorship))
+            .collect::<StdResult<Vec<_>>>()?;
+        for sponsorship in refunded {
+            for amount in sponsorship.amounts {
+                payouts.push((sponsorship.sponsor.to_string(), amount));
+            }
+            settlement.refunded_after = Some(sponsorship.sponsor);
+            settlement.done += 1;
+        }
+    } else {
+        for rank in settlement.done as usize..end as usize {
+            count_win(storage, &raffle.winners[rank])?;
+            for reward in raffle.winner_payouts[rank].iter().filter(|reward| !reward.amount.is_zero()) {
+                payouts.push((raffle.winners[rank].clone(), reward.clone()));
+            }
+        }
+        settlement.done = end;
+    }
+
+    let mut msgs = vec![];
+    for (recipient, amount) in payouts.iter() {
+        let winner = if raffle.cancelled { None } else { Some(recipient.as_str()) };
+        msgs.push(payout_submsg(storage, raffle.id, recipient, &amount.denom, amount.amount, winner)?);
+    }
+    if settlement.done < settlement.total {
+        raffle.settlement = Some(settlement);
+    }
+
+    let payouts = payouts
+        .into_iter()
+        .map(|(recipient, amount)| format!("{}:{}", recipient, asset_string(&amount.denom, amount.amount)))
+        .collect();
+    Ok((msgs, payouts))
+}
+
+// Adds amount to the entry of denom in amounts, or appends one
+fn add_amount(amounts: &mut Vec<AssetAmount>, denom: &Denom, amount: Uint128) {
+    match amounts.iter_mut().find(|entry| entry.denom == *denom) {
+        Some(entry) => entry.amount += amount,
+        None => amounts.push(AssetAmount { denom: denom.clone(), amount }),
+    }
+}
+
+// Takes amount from the entry of denom in amounts, if there is one
+fn sub_amount(amounts: &mut [AssetAmount], denom: &Denom, amount: Uint128) {
+    if let Some(entry) = amounts.iter_mut().find(|entry| entry.denom == *denom) {
+        entry.amount = entry.amount.saturating_sub(amount);
+    }
+}
+
+fn update_player_stats(storage: &mut dyn Storage, player: &Addr, update: impl FnOnce(&mut PlayerStats)) -> StdResult<()> {
+    let mut stats = PLAYER_STATS.may_load(storage, player)?.unwrap_or_default();
+    update(&mut stats);
+    PLAYER_STATS.save(storage, player, &stats)
+}
+
+fn update_global_stats(storage: &mut dyn Storage, update: impl FnOnce(&mut GlobalStats)) -> StdResult<()> {
+    let mut stats = GLOBAL_STATS.may_load(storage)?.unwrap_or_default();
+    update(&mut stats);
+    GLOBAL_STATS.save(storage, &stats)
+}
+
+// Counts a rank paid out or claimed by winner, what it won is credited as each payout arrives
+fn count_win(storage: &mut dyn Storage, winner: &str) -> StdResult<()> {
+    update_player_stats(storage, &Addr::unchecked(winner), |stats| stats.wins += 1)
+}
+
+// Credits a prize that reached its winner to their stats, the leaderboard and the global stats
+fn credit_win(storage: &mut dyn Storage, raffle_id: u32, winner: &str, reward: AssetAmount) -> StdResult<()> {
+    let winner = Addr::unchecked(winner);
+    let mut stats = PLAYER_STATS.may_load(storage, &winner)?.unwrap_or_default();
+    let key = token_key(&reward.denom);
+    let won = stats.won.iter().find(|won| won.denom == reward.denom).map(|won| won.amount).unwrap_or_default();
+    LEADERBOARD.remove(storage, (&key, won.u128(), &winner));
+    LEADERBOARD.save(storage, (&key, (won + reward.amount).u128(), &winner), &(won + reward.amount))?;
+    add_amount(&mut stats.won, &reward.denom, reward.amount);
+    PLAYER_STATS.save(storage, &winner, &stats)?;
+
+    update_global_stats(storage, |global| {
+        add_amount(&mut global.paid_out, &reward.denom, reward.amount);
+        let biggest = BiggestWin { raffle_id, winner: winner.clone(), prize: reward.clone() };
+        match global.biggest_wins.iter_mut().find(|win| win.prize.denom == reward.denom) {
+            Some(win) if win.prize.amount < reward.amount => *win = biggest,
+            Some(_) => {}
+            None => global.biggest_wins.push(biggest),
         }
+    })
+}
+
+// Formats an amount like a Coin, e.g. 10ujuno, using the cw20 address as denom of tokens
+fn asset_string(denom: &Denom, amount: Uint128) -> String {
+    format!("{}{}", amount, token_key(denom))
+}
+
+fn join_strings<T: ToString>(items: impl Iterator<Item = T>) -> String {
+    items.map(|item| item.to_string()).collect::<Vec<_>>().join(",")
+}
+
+// Chains reject empty attribute values, so attributes with nothing to report are left out
+fn non_empty_attributes(attributes: Vec<(&str, String)>) -> Vec<Attribute> {
+    attributes
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| attr(key, value))
+        .collect()
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn retry_payout(
+    deps: DepsMut,
+    info: MessageInfo,
+    payout_id: u64,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let payout = match FAILED_PAYOUTS.may_load(deps.storage, payout_id)? {
+        Some(payout) => payout,
+        None => return Err(ContractError::PayoutNotFound { id: payout_id }),
+    };
+
+    // admins may push the payout again, only its recipient may send it elsewhere
+    let is_recipient = info.sender == payout.recipient;
+    if !is_recipient && (recipient.is_some() || !is_admin(deps.as_ref(), info.sender)?) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?.to_string(),
+        None => payout.recipient,
+    };
+
+    FAILED_PAYOUTS.remove(deps.storage, payout_id);
+    let payout = Payout { recipient, ..payout };
+    PENDING_PAYOUTS.save(deps.storage, payout_id, &payout)?;
+    let msg = SubMsg::reply_always(transfer_msg(&payout.denom, &payout.recipient, payout.amount)?, PAYOUT_REPLY_ID_BASE + payout_id);
+
+    Ok(Response::new()
+        .add_attribute("action", "retry_payout")
+        .add_attribute("payout_id", payout_id.to_string())
+        .add_attribute("raffle_id", payout.raffle_id.to_string())
+        .add_attribute("recipient", payout.recipient)
+        .add_submessage(msg))
+}
+
+// Records a payout and sends it as a submessage whose reply tracks the outcome
+fn payout_submsg(
+    storage: &mut dyn Storage,
+    raffle_id: u32,
+    recipient: &str,
+    denom: &Denom,
+    amount: Uint128,
+    winner: Option<&str>,
+) -> StdResult<SubMsg> {
+    let payout_id = PAYOUT_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    PAYOUT_COUNT.save(storage, &payout_id)?;
+
+    PENDING_PAYOUTS.save(storage, payout_id, &Payout {
+        id: payout_id,
+        raffle_id,
+        recipient: recipient.to_string(),
+        denom: denom.clone(),
+        amount,
+        winner: winner.map(str::to_string),
+    })?;
+
+    Ok(SubMsg::reply_always(transfer_msg(denom, recipient, amount)?, PAYOUT_REPLY_ID_BASE + payout_id))
+}
+
+// Sends amount of denom held by the contract to recipient
+pub fn transfer_msg(denom: &Denom, recipient: &str, amount: Uint128) -> StdResult<CosmosMsg> {
+    match denom {
+        Denom::Native(denom) => Ok(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: denom.clone(),
+                amount,
+            }]
+        }.into()),
+        Denom::Cw20(token_addr) => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token_addr.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient: recipient.to_string(), amount })?,
+            funds: vec![]
+        })),
+    }
+}
+
+// Transfers an NFT prize held by the contract to recipient
+pub fn transfer_nft_msg(prize: &NftPrize, recipient: &str) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: prize.contract.to_string(),
+        msg: to_binary(&Cw721ExecuteMsg::TransferNft { recipient: recipient.to_string(), token_id: prize.token_id.clone() })?,
+        funds: vec![]
+    }))
+}
+
+pub fn update_allowed_denoms(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    if !is_admin(deps.as_ref(), info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut allowed_denoms = ALLOWED_DENOMS.load(deps.storage)?;
+    allowed_denoms.retain(|denom| !remove.contains(denom));
+    for denom in add {
+        if !allowed_denoms.contains(&denom) {
+            allowed_denoms.push(denom);
+        }
+    }
+    // an empty list would open the contract to every denom
+    if allowed_denoms.is_empty() {
+        return Err(ContractError::EmptyAllowlist {});
+    }
+    ALLOWED_DENOMS.save(deps.storage, &allowed_denoms)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_allowed_denoms")
+        .add_attributes(non_empty_attributes(vec![("allowed_denoms", allowed_denoms.join(","))])))
+}
+
+pub fn update_jackpot(
+    deps: DepsMut,
+    info: MessageInfo,
+    series: String,
+    config: Option<JackpotConfig>,
+) -> Result<Response, ContractError> {
+    if !is_admin(deps.as_ref(), info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut res = Response::new()
+        .add_attribute("action", "update_jackpot")
+        .add_attribute("series", series.clone());
+    match config {
+        Some(config) => {
+            if config.share_bps > 10_000 || config.odds == 0 {
+                return Err(ContractError::InvalidJackpot {});
+            }
+            JACKPOT_CONFIGS.save(deps.storage, &series, &config)?;
+            res = res
+                .add_attribute("share_bps", config.share_bps.to_string())
+                .add_attribute("odds", config.odds.to_string());
+        }
+        None => JACKPOT_CONFIGS.remove(deps.storage, &series),
+    }
+
+    Ok(res)
+}
+
+pub fn add_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    if !is_admin(deps.as_ref(), info.sender)? {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let addr = deps.api.addr_validate(&addr)?;
+    HOOKS.add_hook(deps.storage, addr.clone())?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_hook")
+        .add_attribute("hook", addr))
+}
 
+pub fn remove_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    addr: String,
+) -> Result<Response, ContractError> {
+    if !is_admin(deps.as_ref(), info.sender)? {
+        return Err(ContractError::Unauthorized {});
     }
 
-    let data = Raffle {
-        id,
-        begin_time_stamp: raffle.begin_time_stamp,
-        end_time_stamp: raffle.end_time_stamp,
-        players: raffle.players,
-        winners: winner_addresses,
-        minimum_stake: raffle.minimum_stake,
-        winners_distribution: raffle.winners_distribution,
-        winner_payouts: payouts,
-        active: false,
-        pay_token: raffle.pay_token,
-    };
+    let addr = deps.api.addr_validate(&addr)?;
+    HOOKS.remove_hook(deps.storage, addr.clone())?;
 
-    RAFFLEMAP.save(deps.storage, &id.to_string(), &data)?;
+    Ok(Response::new()
+        .add_attribute("action", "remove_hook")
+        .add_attribute("hook", addr))
+}
 
-    Ok(res)
+// One submessage per registered hook, a failing hook is caught in reply and doesn't revert the caller
+fn hook_msgs(deps: Deps, msg: RaffleHookMsg) -> StdResult<Vec<SubMsg>> {
+    HOOKS.prepare_hooks(deps.storage, |hook| {
+        Ok(SubMsg::reply_on_error(msg.clone().into_cosmos_msg(hook)?, HOOK_REPLY_ID).with_gas_limit(HOOK_GAS_LIMIT))
+    })
 }
 
 pub fn is_admin(
@@ -281,18 +1958,12 @@ pub fn is_admin(
 }
 
 fn can_register(deps: Deps, id_lottery: u32) -> Result<bool, ContractError> {
-    let raffle = RAFFLEMAP.load(deps.storage, &id_lottery.to_string())?;
-    return Ok(raffle.active);
+    let raffle = RAFFLEMAP.load(deps.storage, id_lottery)?;
+    Ok(raffle.active)
 }
 
-fn is_registered(deps: Deps, id_lottery: u32, caller: String) -> Result<bool, ContractError> {
-    let raffle = RAFFLEMAP.may_load(deps.storage, &id_lottery.to_string())?;
-
-    if raffle.unwrap().players.contains(&caller) {
-        return Ok(true);
-    } else {
-        return Ok(false);
-    }
+fn is_registered(deps: Deps, id_lottery: u32, caller: &Addr) -> Result<bool, ContractError> {
+    Ok(entries().has(deps.storage, (id_lottery, caller)))
 }
 
 pub fn new_entropy(info: &MessageInfo, env: &Env, seed: &[u8], entropy: &[u8]) -> [u8; 32] {
@@ -300,7 +1971,7 @@ pub fn new_entropy(info: &MessageInfo, env: &Env, seed: &[u8], entropy: &[u8]) -
     let entropy_len = 16 + info.sender.to_string().len() + entropy.len();
     let mut rng_entropy = Vec::with_capacity(entropy_len);
     rng_entropy.extend_from_slice(&env.block.height.to_be_bytes());
-    rng_entropy.extend_from_slice(&info.sender.as_bytes());
+    rng_entropy.extend_from_slice(info.sender.as_bytes());
     rng_entropy.extend_from_slice(entropy);
 
     let mut rng = Prng::new(seed, &rng_entropy);
@@ -311,26 +1982,57 @@ pub fn new_entropy(info: &MessageInfo, env: &Env, seed: &[u8], entropy: &[u8]) -
 #[entry_point]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::GetTotalDeposit { } => to_binary(&query_total_deposit(deps, env)?),
+        QueryMsg::GetTotalDeposit { denom } => to_binary(&query_total_deposit(deps, env, denom)?),
         QueryMsg::GetCount {  } => to_binary(&get_current_counter(deps)?),
-        QueryMsg::GetRaffleInfo { id } => to_binary(&get_raffle_info(deps, id)?)
+        QueryMsg::GetRaffleInfo { id } => to_binary(&get_raffle_info(deps, id)?),
+        QueryMsg::ListRaffles { start_after, limit, status, pay_token, creator, order } => {
+            to_binary(&list_raffles(deps, env, start_after, limit, status, pay_token, creator, order)?)
+        }
+        QueryMsg::GetRaffleSummary { id } => to_binary(&ARCHIVE.load(deps.storage, id)?),
+        QueryMsg::GetAllowedDenoms {  } => to_binary(&ALLOWED_DENOMS.load(deps.storage)?),
+        QueryMsg::GetPot { id } => to_binary(&get_pot(deps, id)?),
+        QueryMsg::GetEntries { id, start_after, limit } => to_binary(&get_entries(deps, id, start_after, limit)?),
+        QueryMsg::GetSponsorships { id, start_after, limit } => to_binary(&get_sponsorships(deps, id, start_after, limit)?),
+        QueryMsg::PlayerRaffles { address, start_after, limit } => to_binary(&player_raffles(deps, address, start_after, limit)?),
+        QueryMsg::SimulateEntry { id, address, tickets, denom } => to_binary(&simulate_entry(deps, id, address, tickets, denom)?),
+        QueryMsg::GlobalStats {} => to_binary(&GLOBAL_STATS.may_load(deps.storage)?.unwrap_or_default()),
+        QueryMsg::Leaderboard { denom, limit } => to_binary(&leaderboard(deps, denom, limit)?),
+        QueryMsg::PlayerStats { address } => {
+            let address = deps.api.addr_validate(&address)?;
+            to_binary(&PLAYER_STATS.may_load(deps.storage, &address)?.unwrap_or_default())
+        }
+        QueryMsg::GetHooks {  } => to_binary(&HOOKS.query_hooks(deps)?),
+        QueryMsg::GetJackpot { series } => to_binary(&JACKPOTS.may_load(deps.storage, &series)?.unwrap_or_default()),
+        QueryMsg::GetJackpotConfig { series } => to_binary(&JACKPOT_CONFIGS.may_load(deps.storage, &series)?),
+        QueryMsg::GetJackpotHits { series, start_after, limit } => to_binary(&get_jackpot_hits(deps, series, start_after, limit)?),
+        QueryMsg::GetFailedPayouts { raffle_id, start_after, limit } => to_binary(&get_failed_payouts(deps, raffle_id, start_after, limit)?),
     }
 }
 
-pub fn query_total_deposit(deps: Deps, env: Env) -> StdResult<Uint128>{
-    let balance: AllBalanceResponse  = deps.querier.query(
-        &QueryRequest::Bank(BankQuery::AllBalances{
-            address: env.contract.address.to_string(),
-        })
-    )?;
-
-    let juno_amount = balance.amount
-     .iter()
-     .find(|c|c.denom =="ujuno".to_string())
-     .map(|c|c.amount)
-     .unwrap_or_else(Uint128::zero);
-    println!("juno amount: {}", juno_amount);
-    Ok(juno_amount)
+pub fn query_total_deposit(deps: Deps, env: Env, denom: Denom) -> StdResult<Uint128>{
+    match denom {
+        Denom::Native(denom) => {
+            let balance: AllBalanceResponse  = deps.querier.query(
+                &QueryRequest::Bank(BankQuery::AllBalances{
+                    address: env.contract.address.to_string(),
+                })
+            )?;
+
+            let amount = balance.amount
+             .iter()
+             .find(|c|c.denom == denom)
+             .map(|c|c.amount)
+             .unwrap_or_else(Uint128::zero);
+            Ok(amount)
+        }
+        Denom::Cw20(token_addr) => {
+            let balance: BalanceResponse = deps.querier.query_wasm_smart(
+                token_addr,
+                &Cw20QueryMsg::Balance { address: env.contract.address.to_string() },
+            )?;
+            Ok(balance.balance)
+        }
+    }
 }
 
 fn get_current_counter(deps: Deps) -> StdResult<u32> {
@@ -339,18 +2041,321 @@ fn get_current_counter(deps: Deps) -> StdResult<u32> {
 }
 
 fn get_raffle_info(deps:Deps, id: u32) -> StdResult<Raffle> {
-    let raffle = RAFFLEMAP.load(deps.storage, &id.to_string())?;
+    let raffle = RAFFLEMAP.load(deps.storage, id)?;
     Ok(raffle)
 }
 
+#[allow(clippy::too_many_arguments)]
+fn list_raffles(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+    status: Option<RaffleStatus>,
+    pay_token: Option<Denom>,
+    creator: Option<String>,
+    order: Option<ListOrder>,
+) -> StdResult<ListRafflesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let creator = creator.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let (start, end, order) = match order.unwrap_or_default() {
+        ListOrder::Ascending => (start_after.map(Bound::exclusive), None, Order::Ascending),
+        ListOrder::Descending => (None, start_after.map(Bound::exclusive), Order::Descending),
+    };
+
+    // a selective filter stops at MAX_LIST_SCAN raffles read, the caller continues from the last one
+    let mut raffles = vec![];
+    let mut last_read = None;
+    for (scanned, item) in RAFFLEMAP.range(deps.storage, start, end, order).enumerate() {
+        if raffles.len() == limit || scanned == MAX_LIST_SCAN {
+            return Ok(ListRafflesResponse { raffles, next_start_after: last_read });
+        }
+        let (id, raffle) = item?;
+        last_read = Some(id);
+
+        let raffle_status = raffle_status(&raffle, &env);
+        let matches = status.map_or(true, |status| status == raffle_status)
+            && pay_token.as_ref().map_or(true, |denom| raffle.asset_index(denom).is_some())
+            && creator.as_ref().map_or(true, |creator| *creator == raffle.creator);
+        if matches {
+            raffles.push(RaffleListItem {
+                id,
+                status: raffle_status,
+                begin_time_stamp: raffle.begin_time_stamp,
+                end_time_stamp: raffle.end_time_stamp,
+                pot: raffle.assets.iter().map(|asset| AssetAmount { denom: asset.denom.clone(), amount: asset.pot }).collect(),
+                entrants: raffle.entrants,
+            });
+        }
+    }
+    Ok(ListRafflesResponse { raffles, next_start_after: None })
+}
+
+fn raffle_status(raffle: &Raffle, env: &Env) -> RaffleStatus {
+    if raffle.settlement.is_some() {
+        RaffleStatus::Settling
+    } else if raffle.cancelled {
+        RaffleStatus::Cancelled
+    } else if !raffle.active {
+        RaffleStatus::Settled
+    } else if raffle.is_expired(&env.block) {
+        RaffleStatus::Ended
+    } else {
+        RaffleStatus::Open
+    }
+}
+
+fn player_raffles(
+    deps: Deps,
+    address: String,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PlayerRaffle>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let player = deps.api.addr_validate(&address)?;
+    let start = start_after.map(|id| Bound::exclusive((id, player.clone())));
+
+    let mut raffles = entries()
+        .idx
+        .player
+        .prefix(player.clone())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (_, entry) = item?;
+            // what the player won in the raffle, whether paid or still to claim, from the summary while archiving
+            let won = match RAFFLEMAP.may_load(deps.storage, entry.raffle_id)? {
+                Some(raffle) => won_by(&player, &raffle.winners, &raffle.winner_payouts),
+                None => {
+                    let summary = ARCHIVE.load(deps.storage, entry.raffle_id)?;
+                    won_by(&player, &summary.winners, &summary.winner_payouts)
+                }
+            };
+            Ok(PlayerRaffle { raffle_id: entry.raffle_id, tickets: entry.tickets, paid: entry.paid, won })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // archived raffles come from the history, an entry is in only one of both
+    let archived = PLAYER_ARCHIVE
+        .prefix(&player)
+        .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(raffle_id, entry)| PlayerRaffle { raffle_id, tickets: entry.tickets, paid: entry.paid, won: entry.won }))
+        .collect::<StdResult<Vec<_>>>()?;
+    raffles.extend(archived);
+    raffles.sort_by_key(|raffle| raffle.raffle_id);
+    raffles.truncate(limit);
+    Ok(raffles)
+}
+
+// What player won in a raffle over all the ranks they hold
+fn won_by(player: &Addr, winners: &[String], winner_payouts: &[Vec<AssetAmount>]) -> Vec<AssetAmount> {
+    let mut won = vec![];
+    for (winner, payout) in winners.iter().zip(winner_payouts.iter()) {
+        if *winner == *player {
+            for amount in payout.iter().filter(|amount| !amount.amount.is_zero()) {
+                add_amount(&mut won, &amount.denom, amount.amount);
+            }
+        }
+    }
+    won
+}
+
+fn leaderboard(deps: Deps, denom: Denom, limit: Option<u32>) -> StdResult<Vec<LeaderboardEntry>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    LEADERBOARD
+        .sub_prefix(&token_key(&denom))
+        .range(deps.storage, None, None, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|((_, address), won)| LeaderboardEntry { address, won }))
+        .collect()
+}
+
+fn simulate_entry(
+    deps: Deps,
+    id: u32,
+    address: String,
+    tickets: u32,
+    denom: Option<Denom>,
+) -> StdResult<SimulateEntryResponse> {
+    let raffle = RAFFLEMAP.load(deps.storage, id)?;
+    let player = deps.api.addr_validate(&address)?;
+    let held = entries().may_load(deps.storage, (id, &player))?.map(|entry| entry.tickets).unwrap_or_default();
+    let asset_index = match &denom {
+        Some(denom) => raffle
+            .asset_index(denom)
+            .ok_or_else(|| StdError::generic_err(format!("Denom not accepted: {}", token_key(denom))))?,
+        None => 0,
+    };
+
+    let asset = &raffle.assets[asset_index];
+    let too_many = || StdError::generic_err("Too many tickets");
+    let cost = AssetAmount { denom: asset.denom.clone(), amount: asset.ticket_price.checked_mul(Uint128::from(tickets))? };
+    let mut after = raffle.clone();
+    after.tickets = after.tickets.checked_add(tickets).ok_or_else(too_many)?;
+    after.assets[asset_index].pot = after.assets[asset_index].pot.checked_add(cost.amount)?;
+
+    Ok(SimulateEntryResponse {
+        current: entry_odds(deps, &raffle, held)?,
+        after: entry_odds(deps, &after, held.checked_add(tickets).ok_or_else(too_many)?)?,
+        cost,
+    })
+}
+
+// Odds of holding tickets in the raffle if it were drawn as it stands, following choose_winners
+fn entry_odds(deps: Deps, raffle: &Raffle, tickets: u32) -> StdResult<EntryOdds> {
+    let total = raffle.tickets;
+    let chance = if total == 0 { Decimal::zero() } else { Decimal::from_ratio(tickets, total) };
+    let rank_tiers: Vec<PrizeTier> = raffle.rank_tiers().into_iter().cloned().collect();
+    let nb_winners = cmp::min(rank_tiers.len(), total as usize);
+    let counted_ranks = match raffle.unawarded {
+        UnawardedPrize::ProRata => nb_winners,
+        _ => rank_tiers.len(),
+    };
+
+    let mut distributable: Vec<Uint128> = raffle.assets.iter().map(|asset| asset.pot).collect();
+    let mut jackpot_pool = vec![];
+    let mut jackpot_odds = 0u32;
+    if let (Some(jackpot), Some(series), true) = (&raffle.jackpot, &raffle.series, total > 0) {
+        jackpot_pool = JACKPOTS.may_load(deps.storage, series)?.unwrap_or_default();
+        jackpot_odds = jackpot.odds;
+        for (index, asset) in raffle.assets.iter().enumerate() {
+            let share = asset.pot.multiply_ratio(jackpot.share_bps, 10_000_u32);
+            distributable[index] -= share;
+            add_amount(&mut jackpot_pool, &asset.denom, share);
+        }
+    }
+
+    let mut rank_prizes = vec![vec![]; rank_tiers.len()];
+    let mut expected_payout = vec![];
+    for (index, asset) in raffle.assets.iter().enumerate().filter(|(_, asset)| !asset.pot.is_zero()) {
+        let (mut amounts, _, remainder) = allocate_prizes(&rank_tiers, counted_ranks, &asset.denom, distributable[index]);
+        if raffle.dust == DustDestination::FirstWinner && nb_winners > 0 {
+            amounts[0] += remainder;
+        }
+        for (rank, amount) in amounts.iter().enumerate().filter(|(_, amount)| !amount.is_zero()) {
+            rank_prizes[rank].push(AssetAmount { denom: asset.denom.clone(), amount: *amount });
+        }
+        let won = amounts[..nb_winners].iter().sum::<Uint128>();
+        if total > 0 && !won.is_zero() {
+            add_amount(&mut expected_payout, &asset.denom, won.multiply_ratio(tickets, total));
+        }
+    }
+
+    let mut jackpot_chance = Decimal::zero();
+    if jackpot_odds > 0 {
+        jackpot_chance = Decimal::from_ratio(tickets, total as u128 * jackpot_odds as u128);
+        for amount in jackpot_pool.iter() {
+            add_amount(&mut expected_payout, &amount.denom, amount.amount.multiply_ratio(tickets, total as u128 * jackpot_odds as u128));
+        }
+    }
+
+    // ranks past the number of tickets aren't drawn
+    let mut tiers = vec![];
+    let mut first_rank = 0;
+    for tier in raffle.prize_tiers.iter() {
+        let drawn = (tier.winners as usize).min(nb_winners.saturating_sub(first_rank)) as u32;
+        let missed = (Decimal::one() - chance).checked_pow(drawn).map_err(StdError::from)?;
+        tiers.push(TierOdds { prize: rank_prizes[first_rank].clone(), chance: Decimal::one() - missed });
+        first_rank += tier.winners as usize;
+    }
+
+    Ok(EntryOdds { tickets, total_tickets: total, tiers, jackpot_chance, expected_payout })
+}
+
+fn get_failed_payouts(
+    deps: Deps,
+    raffle_id: Option<u32>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Payout>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    FAILED_PAYOUTS
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| item.map(|(_, payout)| payout))
+        .filter(|payout| match (payout, raffle_id) {
+            (Ok(payout), Some(raffle_id)) => payout.raffle_id == raffle_id,
+            _ => true,
+        })
+        .take(limit)
+        .collect()
+}
+
+fn get_entries(
+    deps: Deps,
+    id: u32,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Entry>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    entries()
+        .prefix(id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| item.map(|(_, entry)| entry))
+        .take(limit)
+        .collect()
+}
+
+fn get_sponsorships(
+    deps: Deps,
+    id: u32,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Sponsorship>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    SPONSORSHIPS
+        .prefix(id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| item.map(|(_, sponsorship)| sponsorship))
+        .take(limit)
+        .collect()
+}
+
+fn get_jackpot_hits(
+    deps: Deps,
+    series: String,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<Vec<JackpotHit>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    JACKPOT_HITS
+        .prefix(&series)
+        .range(deps.storage, start, None, Order::Ascending)
+        .map(|item| item.map(|(_, hit)| hit))
+        .take(limit)
+        .collect()
+}
+
+fn get_pot(deps: Deps, id: u32) -> StdResult<Vec<AssetAmount>> {
+    let raffle = RAFFLEMAP.load(deps.storage, id)?;
+    Ok(raffle
+        .assets
+        .into_iter()
+        .map(|asset| AssetAmount { denom: asset.denom, amount: asset.pot })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{from_binary, Uint128, Coin};
+    use std::cell::Cell;
+    use cosmwasm_std::{attr, coins, from_binary, to_binary, Addr, BankMsg, CosmosMsg, Deps, Event, Order, QuerierWrapper, Record, StdError, StdResult, Storage, Uint128, Coin};
+    use cw2::{get_contract_version, set_contract_version};
     use crate::ContractError;
-    use crate::contract::{instantiate, execute, query};
-    use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-    use crate::state::Raffle;
+    use crate::contract::{add_entry, allocate_prizes, instantiate, execute, migrate, query, ticket_holder};
+    use crate::msg::{AcceptedAsset, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, ReceiveMsg};
+    use crate::state::{AssetAmount, Counter, DustDestination, Entry, LegacyRaffle, PrizeTier, Raffle, RaffleAsset, Settlement, TierPrize, UnawardedPrize, ADMINS, ALLOWED_DENOMS, COUNTER, LEGACY_RAFFLES, RAFFLEMAP};
+    use cw20::{Cw20ReceiveMsg, Denom};
     
     #[test]
     fn begin_raffle_round() {
@@ -358,16 +2363,23 @@ mod tests {
         let info = mock_info("creator", &[]);
 
         let instantiate_msg = InstantiateMsg {
-            admins: vec!["creator".to_string()]
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
 
         let raffle_msg = ExecuteMsg::BeginRaffleRound {
             expire_type: 0,
-            minimum_stake: Uint128::from(10 as u32),
             winners_distribution: vec![1, 2, 3],
-            pay_token: None
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u32) }],
+            series: None,
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::ProRata,
+            dust: DustDestination::FirstWinner,
         };
         
         let env = mock_env();
@@ -380,14 +2392,34 @@ mod tests {
         assert_eq!(raffle, Raffle {
             id: 0,
             begin_time_stamp: env.block.time,
-            minimum_stake:  Uint128::from(10 as u32),
             end_time_stamp: env.block.time.plus_seconds(1800),
             winners_distribution: vec![1, 2, 3],
-            players: vec![],
+            entrants: 0,
+            tickets: 0,
             winner_payouts: vec![],
             winners: vec![],
             active: true, 
-            pay_token: None, 
+            assets: vec![RaffleAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u32), pot: Uint128::zero() }],
+            series: None,
+            creator: Addr::unchecked("creator"),
+            nft_prizes: vec![],
+            cancelled: false,
+            ticket_nft: None,
+            claim: None,
+            pending_claims: vec![],
+            redraws: vec![],
+            jackpot: None,
+            jackpot_contributions: vec![],
+            unawarded: UnawardedPrize::ProRata,
+            unawarded_amounts: vec![],
+            sponsors: 0,
+            prize_tiers: vec![1, 2, 3].into_iter().map(|shares| PrizeTier { winners: 1, prize: TierPrize::Shares { shares } }).collect(),
+            dust: DustDestination::FirstWinner,
+            dust_amounts: vec![],
+            settlement: None,
+            settled_at: None,
+            seed_hash: None,
+            seeded: vec![],
         });
     } 
 
@@ -397,7 +2429,9 @@ mod tests {
         let info = mock_info("creator", &[]);
 
         let instantiate_msg = InstantiateMsg {
-            admins: vec!["creator".to_string()]
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
@@ -405,16 +2439,21 @@ mod tests {
         // begin a raffle
         let raffle_msg = ExecuteMsg::BeginRaffleRound {
             expire_type: 0,
-            pay_token: None, 
-            minimum_stake: Uint128::from(10 as u32),
             winners_distribution: vec![1, 2, 3],
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u32) }],
+            series: None,
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::ProRata,
+            dust: DustDestination::FirstWinner,
         };
         
         let env = mock_env();
         execute(deps.as_mut(), env.clone(), info, raffle_msg).unwrap();
 
         // join the raffle
-        let join_raffle_msg = ExecuteMsg::JoinRaffleRoundByJuno {
+        let join_raffle_msg = ExecuteMsg::JoinRaffleRound {
             id: 0
         };
 
@@ -426,7 +2465,7 @@ mod tests {
             e => panic!("unexpected error: {}", e),
         }
 
-        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(10 as u128)}]);
+        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(10_u128)}]);
         execute(deps.as_mut(), mock_env(), info, join_raffle_msg.clone()).unwrap();
         
         let query_msg = QueryMsg::GetRaffleInfo { id: 0};
@@ -436,24 +2475,44 @@ mod tests {
         assert_eq!(raffle, Raffle {
             id: 0,
             begin_time_stamp: env.block.time,
-            minimum_stake:  Uint128::from(10 as u32),
             end_time_stamp: env.block.time.plus_seconds(1800),
             winners_distribution: vec![1, 2, 3],
-            players: vec!["player".to_string()],
+            entrants: 1,
+            tickets: 1,
             winner_payouts: vec![],
             winners: vec![],
             active: true,
-            pay_token: None
+            assets: vec![RaffleAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u32), pot: Uint128::from(10_u128) }],
+            series: None,
+            creator: Addr::unchecked("creator"),
+            nft_prizes: vec![],
+            cancelled: false,
+            ticket_nft: None,
+            claim: None,
+            pending_claims: vec![],
+            redraws: vec![],
+            jackpot: None,
+            jackpot_contributions: vec![],
+            unawarded: UnawardedPrize::ProRata,
+            unawarded_amounts: vec![],
+            sponsors: 0,
+            prize_tiers: vec![1, 2, 3].into_iter().map(|shares| PrizeTier { winners: 1, prize: TierPrize::Shares { shares } }).collect(),
+            dust: DustDestination::FirstWinner,
+            dust_amounts: vec![],
+            settlement: None,
+            settled_at: None,
+            seed_hash: None,
+            seeded: vec![],
         });
 
-        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(10 as u128)}]);
+        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(10_u128)}]);
         let err = execute(deps.as_mut(), mock_env(), info, join_raffle_msg.clone()).unwrap_err();
         match err {
             ContractError::AlreadyRegistered { } => { },
             e => panic!("unexpected error: {}", e),
         }
 
-        let info = mock_info("player1", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(1 as u128)}]);
+        let info = mock_info("player1", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(1_u128)}]);
         let err = execute(deps.as_mut(), mock_env(), info, join_raffle_msg).unwrap_err();
         match err {
             ContractError::NotSufficientFunds { } => { },
@@ -461,13 +2520,335 @@ mod tests {
         }   
     } 
 
+    #[test]
+    fn buy_tickets_limits() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg {
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
+        }).unwrap();
+
+        for ticket_nft in [None, Some("tickets".to_string())] {
+            let raffle_msg = ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(Addr::unchecked("token")), ticket_price: Uint128::from(1_u32) }],
+                series: None,
+                ticket_nft,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            };
+            execute(deps.as_mut(), mock_env(), info.clone(), raffle_msg).unwrap();
+        }
+
+        let buy = |id: u32, amount: u128| ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "player".to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&ReceiveMsg::BuyTickets { id }).unwrap(),
+        });
+
+        // more tickets than a u32 holds are refused instead of truncated
+        let err = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), buy(0, 1 << 32)).unwrap_err();
+        match err {
+            ContractError::TooManyTickets { max: u32::MAX } => {},
+            e => panic!("unexpected error: {}", e),
+        }
+
+        // every ticket NFT is minted in the purchase
+        let err = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), buy(1, 101)).unwrap_err();
+        match err {
+            ContractError::TooManyTickets { max: 100 } => {},
+            e => panic!("unexpected error: {}", e),
+        }
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), buy(1, 100)).unwrap();
+        assert_eq!(res.messages.len(), 100);
+    }
+
+    #[test]
+    fn migrate_legacy_raffles() {
+        // a 0.1 deployment: duplicated admins, no version, treasury or allowlist, raffles keyed by
+        // their id as a string and the counter at the last id given out
+        let mut deps = mock_dependencies();
+        let admins = vec![Addr::unchecked("creator"), Addr::unchecked("creator")];
+        ADMINS.save(&mut deps.storage, &admins).unwrap();
+        let legacy = LegacyRaffle {
+            id: 0,
+            begin_time_stamp: mock_env().block.time,
+            end_time_stamp: mock_env().block.time.plus_seconds(1800),
+            minimum_stake: Uint128::from(10_u128),
+            winners_distribution: vec![1],
+            players: vec!["alice".to_string(), "bob".to_string(), "alice".to_string()],
+            winner_payouts: vec![],
+            winners: vec![],
+            active: true,
+            pay_token: None,
+        };
+        for id in [0, 2, 10] {
+            let raffle = LegacyRaffle { id, ..legacy.clone() };
+            LEGACY_RAFFLES.save(&mut deps.storage, &id.to_string(), &raffle).unwrap();
+        }
+        COUNTER.save(&mut deps.storage, &Counter { counter: 10 }).unwrap();
+
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+        match err {
+            ContractError::MissingTreasury { } => { },
+            e => panic!("unexpected error: {}", e),
+        }
+        let msg = MigrateMsg { treasury: Some("treasury".to_string()), allowed_denoms: None };
+        let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.attributes, vec![
+            attr("action", "migrate"),
+            attr("from_version", "0.1.0"),
+            attr("to_version", env!("CARGO_PKG_VERSION")),
+            attr("migrated_raffles", "3"),
+        ]);
+        assert_eq!(get_contract_version(&deps.storage).unwrap().contract, "crates.io:fury");
+        assert_eq!(ADMINS.load(&deps.storage).unwrap(), vec![Addr::unchecked("creator")]);
+        assert_eq!(ALLOWED_DENOMS.load(&deps.storage).unwrap(), vec!["ujuno".to_string()]);
+
+        // raffles now come back in id order, and the next one gets the id after the last
+        let ids = RAFFLEMAP.keys(&deps.storage, None, None, Order::Ascending).collect::<StdResult<Vec<_>>>().unwrap();
+        assert_eq!(ids, vec![0, 2, 10]);
+        assert!(LEGACY_RAFFLES.keys(&deps.storage, None, None, Order::Ascending).next().is_none());
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetCount {}).unwrap();
+        assert_eq!(from_binary::<u32>(&res).unwrap(), 11);
+
+        let raffle = RAFFLEMAP.load(&deps.storage, 0).unwrap();
+        assert_eq!((raffle.entrants, raffle.tickets), (2, 3));
+        assert_eq!(raffle.creator, Addr::unchecked("creator"));
+        assert_eq!(raffle.assets, vec![RaffleAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u128), pot: Uint128::from(30_u128) }]);
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetEntries { id: 0, start_after: None, limit: None }).unwrap();
+        let entries: Vec<Entry> = from_binary(&res).unwrap();
+        let paid = |amount: u128| vec![AssetAmount { denom: Denom::Native("ujuno".to_string()), amount: Uint128::from(amount) }];
+        assert_eq!(entries, vec![
+            Entry { raffle_id: 0, player: Addr::unchecked("alice"), tickets: 2, paid: paid(20) },
+            Entry { raffle_id: 0, player: Addr::unchecked("bob"), tickets: 1, paid: paid(10) },
+        ]);
+
+        // already registered players still can't join twice, and migrating again changes nothing
+        let info = mock_info("bob", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(10_u128)}]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::JoinRaffleRound { id: 0 }).unwrap_err();
+        match err {
+            ContractError::AlreadyRegistered { } => { },
+            e => panic!("unexpected error: {}", e),
+        }
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+        assert_eq!(res.attributes[1], attr("from_version", env!("CARGO_PKG_VERSION")));
+        assert_eq!(res.attributes[3], attr("migrated_raffles", "0"));
+    }
+
+    #[test]
+    fn migrate_pays_ended_legacy_raffles() {
+        // 0.1 drew winners but its payout messages never left the contract
+        let mut deps = mock_dependencies();
+        ADMINS.save(&mut deps.storage, &vec![Addr::unchecked("creator")]).unwrap();
+        let legacy = LegacyRaffle {
+            id: 0,
+            begin_time_stamp: mock_env().block.time,
+            end_time_stamp: mock_env().block.time.plus_seconds(1800),
+            minimum_stake: Uint128::from(10_u128),
+            winners_distribution: vec![2, 1],
+            players: vec!["alice".to_string(), "bob".to_string(), "carol".to_string()],
+            winner_payouts: vec![Uint128::from(20_u128), Uint128::from(10_u128)],
+            winners: vec!["bob".to_string(), "alice".to_string()],
+            active: false,
+            pay_token: None,
+        };
+        LEGACY_RAFFLES.save(&mut deps.storage, "0", &legacy).unwrap();
+        let msg = MigrateMsg { treasury: Some("treasury".to_string()), allowed_denoms: None };
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let raffle = RAFFLEMAP.load(&deps.storage, 0).unwrap();
+        assert_eq!(raffle.settlement, Some(Settlement { done: 0, total: 2, refunded_after: None }));
+        assert_eq!(raffle.settled_at, Some(legacy.end_time_stamp));
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::ContinueSettlement { id: 0, limit: None }).unwrap();
+        let sent: Vec<CosmosMsg> = res.messages.into_iter().map(|msg| msg.msg).collect();
+        assert_eq!(sent, vec![
+            BankMsg::Send { to_address: "bob".to_string(), amount: coins(20, "ujuno") }.into(),
+            BankMsg::Send { to_address: "alice".to_string(), amount: coins(10, "ujuno") }.into(),
+        ]);
+        assert_eq!(RAFFLEMAP.load(&deps.storage, 0).unwrap().settlement, None);
+    }
+
+    #[test]
+    fn migrate_checks_version() {
+        let mut deps = mock_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), instantiate_msg).unwrap();
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!((version.contract.as_str(), version.version.as_str()), ("crates.io:fury", env!("CARGO_PKG_VERSION")));
+
+        set_contract_version(&mut deps.storage, "crates.io:fury", "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+        match err {
+            ContractError::CannotDowngrade { from, .. } => assert_eq!(from, "99.0.0"),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+        match err {
+            ContractError::WrongContract { found, .. } => assert_eq!(found, "crates.io:cw20-base"),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        set_contract_version(&mut deps.storage, "crates.io:fury", "latest").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap_err();
+        match err {
+            ContractError::InvalidVersion { version } => assert_eq!(version, "latest"),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    // Storage that counts what a lookup reads, entries pulled from a range each count as one read
+    struct CountingStorage<'a> {
+        inner: &'a dyn Storage,
+        reads: Cell<u32>,
+    }
+
+    impl Storage for CountingStorage<'_> {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.reads.set(self.reads.get() + 1);
+            self.inner.get(key)
+        }
+
+        fn range<'a>(&'a self, start: Option<&[u8]>, end: Option<&[u8]>, order: Order) -> Box<dyn Iterator<Item = Record> + 'a> {
+            Box::new(self.inner.range(start, end, order).inspect(move |_| self.reads.set(self.reads.get() + 1)))
+        }
+
+        fn set(&mut self, _key: &[u8], _value: &[u8]) {
+            panic!("lookups don't write");
+        }
+
+        fn remove(&mut self, _key: &[u8]) {
+            panic!("lookups don't write");
+        }
+    }
+
+    #[test]
+    fn ticket_lookup_scales() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let instantiate_msg = InstantiateMsg {
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec!["ujuno".to_string()],
+            treasury: "treasury".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+        let raffle_msg = ExecuteMsg::BeginRaffleRound {
+            expire_type: 0,
+            winners_distribution: vec![1],
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u32) }],
+            series: None,
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::ProRata,
+            dust: DustDestination::FirstWinner,
+        };
+        execute(deps.as_mut(), mock_env(), info, raffle_msg).unwrap();
+
+        // 12k purchases of 1 to 7 tickets each
+        let mut raffle = RAFFLEMAP.load(&deps.storage, 0).unwrap();
+        let mut ends = vec![];
+        for i in 0..12_000u32 {
+            let tickets = i % 7 + 1;
+            let player = Addr::unchecked(format!("player{}", i));
+            add_entry(&mut deps.storage, &mut raffle, &player, 0, Uint128::from(10 * tickets), tickets).unwrap();
+            ends.push(raffle.tickets);
+        }
+        assert_eq!(raffle.tickets, ends[ends.len() - 1]);
+
+        // every ticket at a range boundary and a spread of others, each found in a single range read
+        let mut samples: Vec<u32> = (0..raffle.tickets).step_by(997).collect();
+        samples.extend([0, 1, ends[0] - 1, ends[0], raffle.tickets - 1]);
+        let storage = CountingStorage { inner: &deps.storage, reads: Cell::new(0) };
+        let counted = Deps { storage: &storage, api: &deps.api, querier: QuerierWrapper::new(&deps.querier) };
+        for ticket in samples {
+            storage.reads.set(0);
+            let holder = ticket_holder(counted, &raffle, ticket).unwrap();
+            let purchase = ends.partition_point(|end| *end <= ticket);
+            assert_eq!(holder, format!("player{}", purchase));
+            assert_eq!(storage.reads.get(), 1);
+        }
+
+        let err = ticket_holder(counted, &raffle, raffle.tickets).unwrap_err();
+        assert!(matches!(err, StdError::NotFound { .. }));
+    }
+
+    #[test]
+    fn purchases_keep_earlier_tickets() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let instantiate_msg = InstantiateMsg {
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+        let raffle_msg = ExecuteMsg::BeginRaffleRound {
+            expire_type: 0,
+            winners_distribution: vec![1],
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(Addr::unchecked("token")), ticket_price: Uint128::from(1_u32) }],
+            series: None,
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::ProRata,
+            dust: DustDestination::FirstWinner,
+        };
+        execute(deps.as_mut(), mock_env(), info, raffle_msg).unwrap();
+
+        let mut raffle = RAFFLEMAP.load(&deps.storage, 0).unwrap();
+        let alice = Addr::unchecked("alice");
+        let mallory = Addr::unchecked("mallory");
+        add_entry(&mut deps.storage, &mut raffle, &alice, 0, Uint128::from(10_u32), 10).unwrap();
+        RAFFLEMAP.save(&mut deps.storage, 0, &raffle).unwrap();
+
+        // an entry without tickets would have ended at alice's last ticket
+        add_entry(&mut deps.storage, &mut raffle.clone(), &mallory, 0, Uint128::zero(), 0).unwrap_err();
+
+        // nor can a purchase overflowing the ticket count, truncated or not
+        let buy = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: mallory.to_string(),
+            amount: Uint128::new(1 << 32),
+            msg: to_binary(&ReceiveMsg::BuyTickets { id: 0 }).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("token", &[]), buy).unwrap_err();
+
+        let buy = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: mallory.to_string(),
+            amount: Uint128::new(2),
+            msg: to_binary(&ReceiveMsg::BuyTickets { id: 0 }).unwrap(),
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("token", &[]), buy).unwrap();
+
+        let raffle = RAFFLEMAP.load(&deps.storage, 0).unwrap();
+        assert_eq!(raffle.tickets, 12);
+        for ticket in 0..12 {
+            let holder = ticket_holder(deps.as_ref(), &raffle, ticket).unwrap();
+            assert_eq!(holder, if ticket < 10 { "alice" } else { "mallory" });
+        }
+    }
+
     #[test]
     fn choose_winners() {
         let mut deps = mock_dependencies();
         let info = mock_info("creator", &[]);
 
         let instantiate_msg = InstantiateMsg {
-            admins: vec!["creator".to_string()]
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
@@ -475,26 +2856,31 @@ mod tests {
         // begin a raffle
         let raffle_msg = ExecuteMsg::BeginRaffleRound {
             expire_type: 0,
-            pay_token: None, 
-            minimum_stake: Uint128::from(10 as u32),
-            winners_distribution: vec![5, 3, 2]
+            winners_distribution: vec![5, 3, 2],
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u32) }],
+            series: None,
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::ProRata,
+            dust: DustDestination::FirstWinner,
         };
         
         let env = mock_env();
         execute(deps.as_mut(), env.clone(), info, raffle_msg).unwrap();
 
         // join the raffle
-        let join_raffle_msg = ExecuteMsg::JoinRaffleRoundByJuno  {
+        let join_raffle_msg = ExecuteMsg::JoinRaffleRound  {
             id: 0
         };
-        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(1_000_000 as u128)}]);
+        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(1_000_000_u128)}]);
         execute(deps.as_mut(), mock_env(), info, join_raffle_msg.clone()).unwrap();
      
         // join the raffle #2
-        let join_raffle_msg = ExecuteMsg::JoinRaffleRoundByJuno {
+        let join_raffle_msg = ExecuteMsg::JoinRaffleRound {
             id: 0
         };
-        let info = mock_info("player2", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(2_000_000 as u128)}]);
+        let info = mock_info("player2", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(2_000_000_u128)}]);
         execute(deps.as_mut(), mock_env(), info, join_raffle_msg.clone()).unwrap();
 
         // end the raffle
@@ -502,7 +2888,15 @@ mod tests {
             id: 0
         };
         let info = mock_info("creator", &[]);
-        execute(deps.as_mut(), mock_env(), info, end_raffle_msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), end_raffle_msg.clone()).unwrap_err();
+        match err {
+            ContractError::RaffleNotEnded { } => { },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(1800);
+        execute(deps.as_mut(), env, info, end_raffle_msg).unwrap();
         
         // let query_msg = QueryMsg::GetRaffleInfo { id: 0};
         // let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
@@ -511,14 +2905,278 @@ mod tests {
         // assert_eq!(raffle, Raffle {
         //     id: 0,
         //     begin_time_stamp: env.block.time,
-        //     minimum_stake:  Uint128::from(500_000 as u32),
+        //     minimum_stake:  Uint128::from(500_000_u32),
         //     end_time_stamp: Timestamp::from_nanos(2_000_000_000_000_000_000),
         //     winners_distribution: vec![5, 3, 2],
         //     players: vec!["player".to_string()],
-        //     winner_payouts: vec![Uint128::from(10 as u32)],
+        //     winner_payouts: vec![Uint128::from(10_u32)],
         //     winners: vec!["player".to_string()],
         //     active: false 
         // });
     } 
-}
 
+    #[test]
+    fn ibc_denom_raffle() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let ibc_denom = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2".to_string();
+
+        let instantiate_msg = InstantiateMsg {
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec!["ujuno".to_string()],
+            treasury: "treasury".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+
+        let raffle_msg = ExecuteMsg::BeginRaffleRound {
+            expire_type: 0,
+            winners_distribution: vec![1],
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Native(ibc_denom.clone()), ticket_price: Uint128::from(10_u32) }],
+            series: None,
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::ProRata,
+            dust: DustDestination::FirstWinner,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), raffle_msg.clone()).unwrap_err();
+        match err {
+            ContractError::DenomNotAllowed { denom } => assert_eq!(denom, ibc_denom),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        // only admins manage the allowlist
+        let update_msg = ExecuteMsg::UpdateAllowedDenoms { add: vec![ibc_denom.clone()], remove: vec!["ujuno".to_string()] };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("player", &[]), update_msg.clone()).unwrap_err();
+        match err {
+            ContractError::Unauthorized { } => { },
+            e => panic!("unexpected error: {}", e),
+        }
+        execute(deps.as_mut(), mock_env(), info.clone(), update_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAllowedDenoms {}).unwrap();
+        let allowed: Vec<String> = from_binary(&res).unwrap();
+        assert_eq!(allowed, vec![ibc_denom.clone()]);
+
+        // removing the last denom doesn't turn the allowlist into allow-all
+        let update_msg = ExecuteMsg::UpdateAllowedDenoms { add: vec![], remove: vec![ibc_denom.clone()] };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), update_msg).unwrap_err();
+        match err {
+            ContractError::EmptyAllowlist { } => { },
+            e => panic!("unexpected error: {}", e),
+        }
+
+        execute(deps.as_mut(), mock_env(), info, raffle_msg).unwrap();
+
+        let join_raffle_msg = ExecuteMsg::JoinRaffleRound { id: 0 };
+        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(10_u128)}]);
+        let err = execute(deps.as_mut(), mock_env(), info, join_raffle_msg.clone()).unwrap_err();
+        match err {
+            ContractError::DenomNotAccepted { denom } => assert_eq!(denom, "ujuno"),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let info = mock_info("player", &[Coin{ denom: ibc_denom.clone(), amount: Uint128::from(10_u128)}]);
+        execute(deps.as_mut(), mock_env(), info, join_raffle_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetRaffleInfo { id: 0 }).unwrap();
+        let raffle: Raffle = from_binary(&res).unwrap();
+        assert_eq!(raffle.tickets, 1);
+        assert_eq!(raffle.assets[0].pot, Uint128::from(10_u128));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetEntries { id: 0, start_after: None, limit: None }).unwrap();
+        let entries: Vec<Entry> = from_binary(&res).unwrap();
+        assert_eq!(entries, vec![Entry {
+            raffle_id: 0,
+            player: Addr::unchecked("player"),
+            tickets: 1,
+            paid: vec![AssetAmount { denom: Denom::Native(ibc_denom), amount: Uint128::from(10_u128) }],
+        }]);
+    }
+
+    #[test]
+    fn hooks_have_gas_limit() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info.clone(), InstantiateMsg {
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
+        }).unwrap();
+        execute(deps.as_mut(), mock_env(), info.clone(), ExecuteMsg::AddHook { addr: "hook".to_string() }).unwrap();
+
+        let raffle_msg = ExecuteMsg::BeginRaffleRound {
+            expire_type: 0,
+            winners_distribution: vec![1],
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(10_u32) }],
+            series: None,
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::ProRata,
+            dust: DustDestination::FirstWinner,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, raffle_msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].gas_limit, Some(300_000));
+    }
+
+    #[test]
+    fn events() {
+        let mut deps = mock_dependencies();
+        let info = mock_info("creator", &[]);
+
+        let instantiate_msg = InstantiateMsg {
+            admins: vec!["creator".to_string()],
+            allowed_denoms: vec![],
+            treasury: "treasury".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), info.clone(), instantiate_msg).unwrap();
+
+        let raffle_msg = ExecuteMsg::BeginRaffleRound {
+            expire_type: 0,
+            winners_distribution: vec![3, 1],
+            prize_tiers: vec![],
+            accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::from(40_u32) }],
+            series: Some("weekly".to_string()),
+            ticket_nft: None,
+            claim: None,
+            unawarded: UnawardedPrize::NextRound,
+            dust: DustDestination::FirstWinner,
+        };
+        let env = mock_env();
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), raffle_msg).unwrap();
+        assert_eq!(res.attributes, vec![attr("action", "begin_raffle_round"), attr("raffle_id", "0")]);
+        assert_eq!(res.events, vec![Event::new("raffle_opened")
+            .add_attribute("raffle_id", "0")
+            .add_attribute("creator", "creator")
+            .add_attribute("end_time", env.block.time.plus_seconds(1800).seconds().to_string())
+            .add_attribute("ticket_prices", "40ujuno")
+            .add_attribute("series", "weekly")]);
+
+        let join_raffle_msg = ExecuteMsg::JoinRaffleRound { id: 0 };
+        let info = mock_info("player", &[Coin{ denom: "ujuno".to_string(), amount: Uint128::from(40_u128)}]);
+        let res = execute(deps.as_mut(), mock_env(), info, join_raffle_msg).unwrap();
+        assert_eq!(res.attributes, vec![attr("action", "join_raffle_round"), attr("raffle_id", "0")]);
+        assert_eq!(res.events, vec![Event::new("raffle_entered")
+            .add_attribute("raffle_id", "0")
+            .add_attribute("player", "player")
+            .add_attribute("amount", "40")
+            .add_attribute("denom", "ujuno")
+            .add_attribute("tickets", "1")]);
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(1800);
+        let res = execute(deps.as_mut(), env, mock_info("creator", &[]), ExecuteMsg::EndRaffleRound { id: 0 }).unwrap();
+        assert_eq!(res.attributes, vec![attr("action", "end_raffle_round"), attr("raffle_id", "0")]);
+        assert_eq!(res.events.len(), 2);
+        let settled = &res.events[0];
+        assert_eq!(settled.ty, "raffle_settled");
+        assert_eq!(settled.attributes[..5], [
+            attr("raffle_id", "0"),
+            attr("tickets", "1"),
+            attr("pot", "40ujuno"),
+            attr("winners", "player"),
+            attr("unawarded", "10ujuno"),
+        ]);
+        assert_eq!(settled.attributes[5].key, "seed_hash");
+        assert_eq!(settled.attributes[5].value.len(), 64);
+        assert_eq!(res.events[1], Event::new("raffle_winner")
+            .add_attribute("raffle_id", "0")
+            .add_attribute("rank", "0")
+            .add_attribute("winner", "player")
+            .add_attribute("payout", "30ujuno"));
+    }
+
+    #[test]
+    fn prize_tiers() {
+        let juno = Denom::Native("ujuno".to_string());
+        let fixed = |amount: u128| TierPrize::Fixed { amounts: vec![AssetAmount { denom: juno.clone(), amount: Uint128::from(amount) }] };
+        let tiers = [
+            PrizeTier { winners: 1, prize: fixed(100) },
+            PrizeTier { winners: 2, prize: fixed(20) },
+            PrizeTier { winners: 2, prize: TierPrize::Percent { bps: 5000 } },
+            PrizeTier { winners: 3, prize: TierPrize::Shares { shares: 1 } },
+        ];
+        let rank_tiers: Vec<PrizeTier> = tiers.iter().flat_map(|tier| vec![tier.clone(); tier.winners as usize]).collect();
+        let amounts = |amounts: &[u128]| amounts.iter().map(|amount| Uint128::from(*amount)).collect::<Vec<_>>();
+
+        // fixed first, half of the 860 left shared by the percent tier, the 430 left split in three
+        let (allocated, unallocated, _) = allocate_prizes(&rank_tiers, 8, &juno, Uint128::from(1_000_u128));
+        assert_eq!(allocated, amounts(&[100, 20, 20, 215, 215, 143, 143, 143]));
+        assert!(unallocated.is_zero());
+
+        // a pot too small for the fixed prizes is cut at the first one it can't cover
+        let (allocated, _, _) = allocate_prizes(&rank_tiers, 8, &juno, Uint128::from(110_u128));
+        assert_eq!(allocated, amounts(&[100, 10, 0, 0, 0, 0, 0, 0]));
+
+        // ranks past the counted ones get nothing, and without share ranks the rest isn't allocated
+        let (allocated, unallocated, _) = allocate_prizes(&rank_tiers, 4, &juno, Uint128::from(1_000_u128));
+        assert_eq!(allocated, amounts(&[100, 20, 20, 215, 0, 0, 0, 0]));
+        assert_eq!(unallocated, Uint128::from(645_u128));
+
+        // fixed amounts in other assets don't touch this one
+        let (allocated, _, _) = allocate_prizes(&rank_tiers, 8, &Denom::Native("uatom".to_string()), Uint128::from(1_000_u128));
+        assert_eq!(allocated, amounts(&[0, 0, 0, 250, 250, 166, 166, 166]));
+    }
+
+    #[test]
+    fn allocation_adds_up() {
+        use rand_chacha::ChaChaRng;
+        use rand_core::{RngCore, SeedableRng};
+
+        let juno = Denom::Native("ujuno".to_string());
+        let mut rng = ChaChaRng::from_seed([7; 32]);
+        for round in 0..3_000 {
+            let mut rank_tiers = vec![];
+            let mut bps_left = 10_000;
+            for _ in 0..1 + rng.next_u32() % 6 {
+                let winners = 1 + rng.next_u32() % 4;
+                // mixed tiers, then fixed ones only, then percent ones only
+                let kind = match round % 3 {
+                    0 => rng.next_u32() % 3,
+                    1 => 0,
+                    _ if bps_left > 0 => 1,
+                    _ => continue,
+                };
+                let prize = match kind {
+                    0 => TierPrize::Fixed { amounts: vec![AssetAmount { denom: juno.clone(), amount: Uint128::from(rng.next_u32() % 500) }] },
+                    1 if bps_left > 0 => {
+                        let bps = 1 + rng.next_u32() % bps_left;
+                        bps_left -= bps;
+                        TierPrize::Percent { bps }
+                    }
+                    _ => TierPrize::Shares { shares: 1 + rng.next_u32() % 10 },
+                };
+                rank_tiers.extend(vec![PrizeTier { winners, prize }; winners as usize]);
+            }
+            let counted_ranks = (rng.next_u32() as usize) % (rank_tiers.len() + 1);
+            let distributable = Uint128::from(rng.next_u64() % 1_000_000);
+
+            let (amounts, unallocated, dust) = allocate_prizes(&rank_tiers, counted_ranks, &juno, distributable);
+            assert_eq!(amounts.iter().sum::<Uint128>() + unallocated + dust, distributable);
+            assert!(amounts[counted_ranks..].iter().all(|amount| amount.is_zero()));
+            let total_shares: u32 = rank_tiers.iter().take(counted_ranks).map(|tier| match tier.prize {
+                TierPrize::Shares { shares } => shares,
+                _ => 0,
+            }).sum();
+            // without shares only percent tiers leave dust, less than one token per rank
+            let percent_ranks = rank_tiers.iter().filter(|tier| matches!(tier.prize, TierPrize::Percent { .. })).count() as u32;
+            let dust_bound = if total_shares > 0 { total_shares } else { percent_ranks };
+            assert!(dust < Uint128::from(dust_bound.max(1)));
+        }
+
+        // the rounding of a percent tier is dust, not an unawarded prize
+        let rank_tiers = vec![PrizeTier { winners: 3, prize: TierPrize::Percent { bps: 10_000 } }; 3];
+        let (amounts, unallocated, dust) = allocate_prizes(&rank_tiers, 3, &juno, Uint128::new(40));
+        assert_eq!(amounts, vec![Uint128::new(13); 3]);
+        assert_eq!((unallocated, dust), (Uint128::zero(), Uint128::new(1)));
+
+        // fixed prizes leave what they don't take unallocated
+        let fixed = TierPrize::Fixed { amounts: vec![AssetAmount { denom: juno.clone(), amount: Uint128::new(15) }] };
+        let rank_tiers = vec![PrizeTier { winners: 2, prize: fixed }; 2];
+        let (amounts, unallocated, dust) = allocate_prizes(&rank_tiers, 2, &juno, Uint128::new(40));
+        assert_eq!(amounts, vec![Uint128::new(15); 2]);
+        assert_eq!((unallocated, dust), (Uint128::new(10), Uint128::zero()));
+    }
+}
@@ -1,4 +1,5 @@
 use cosmwasm_std::StdError;
+use cw_controllers::HookError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +7,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Hook(#[from] HookError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -22,8 +26,8 @@ pub enum ContractError {
     #[error("Wrong Payment")]
     WrongPayment {},
 
-    #[error("Must pay by Juno")]
-    MustPayByJuno {},
+    #[error("Denom not accepted by this raffle: {denom}")]
+    DenomNotAccepted { denom: String },
 
     #[error("Not sufficient funds")]
     NotSufficientFunds {},
@@ -34,9 +38,87 @@ pub enum ContractError {
     #[error("Raffle not ended")]
     RaffleNotEnded {},
 
+    #[error("Raffle already settled or cancelled")]
+    RaffleClosed {},
+
+    #[error("No prize rank {rank} in this raffle")]
+    InvalidPrizeRank { rank: u32 },
+
     #[error("Must pay by cw20 tokens")]
     MustPayByToken{},
 
+    #[error("Accepted assets must be distinct and have a ticket price")]
+    InvalidAssets {},
+
+    #[error("Claim period must be positive, rollover needs a series")]
+    InvalidClaimConfig {},
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("Claim period is over")]
+    ClaimExpired {},
+
+    #[error("No claim has expired")]
+    NoExpiredClaims {},
+
+    #[error("Claim of rank {rank} hasn't expired")]
+    ClaimNotExpired { rank: u32 },
+
+    #[error("Raffle doesn't redraw unclaimed prizes")]
+    RedrawNotAllowed {},
+
+    #[error("Prize tiers need winners, shares that add up to a u32, at most 10000 bps in all and fixed amounts in accepted assets")]
+    InvalidPrizeTiers {},
+
+    #[error("Pro rata unawarded prizes need a share tier to grow")]
+    ProRataNeedsShares {},
+
+    #[error("Sending prizes or dust to the next round needs a series")]
+    NextRoundNeedsSeries {},
+
+    #[error("Jackpot share must be at most 10000 bps and odds positive")]
+    InvalidJackpot {},
+
+    #[error("Raffle can only be archived 30 days after settling, with nothing left to pay or claim")]
+    NotArchivable {},
+
+    #[error("Raffle is already archived")]
+    AlreadyArchived {},
+
+    #[error("Raffle has nothing left to settle")]
+    NothingToSettle {},
+
+    #[error("Raffle is still settling, continue its settlement first")]
+    SettlementInProgress {},
+
+    #[error("No failed payout with id {id}")]
+    PayoutNotFound { id: u64 },
+
+    #[error("At most {max} tickets can be bought at once")]
+    TooManyTickets { max: u32 },
+
+    #[error("A raffle can have at most {max} winners")]
+    TooManyRanks { max: u32 },
+
     #[error("Expire setting is wrong")]
-    WrongExpire {}
+    WrongExpire {},
+
+    #[error("The allowlist cannot be emptied, an empty list allows any denom")]
+    EmptyAllowlist {},
+
+    #[error("Denom not allowed: {denom}")]
+    DenomNotAllowed { denom: String },
+
+    #[error("Cannot migrate contract {found}, expected {expected}")]
+    WrongContract { expected: String, found: String },
+
+    #[error("Cannot migrate from version {from} down to {to}")]
+    CannotDowngrade { from: String, to: String },
+
+    #[error("Invalid contract version: {version}")]
+    InvalidVersion { version: String },
+
+    #[error("Migrating from 0.1 needs a treasury")]
+    MissingTreasury {},
 }
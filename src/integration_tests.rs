@@ -0,0 +1,1717 @@
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coins, to_binary, Addr, Binary, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult, Uint128};
+    use cw20::{Cw20Coin, Cw20ExecuteMsg, Denom};
+    use cw721::{Cw721QueryMsg, NftInfoResponse, OwnerOfResponse};
+    use cw721_base::{Cw721Contract, Extension, MintMsg};
+    use cw4::HooksResponse;
+    use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+    use cw_storage_plus::Item;
+
+    use crate::msg::{
+        AcceptedAsset, EntryOdds, ExecuteMsg, InstantiateMsg, LeaderboardEntry, ListOrder, ListRafflesResponse, PlayerRaffle, QueryMsg, RaffleHookExecuteMsg, RaffleHookMsg, RaffleListItem, RaffleStatus,
+        ReceiveMsg, ReceiveNftMsg, SimulateEntryResponse, TicketMetadata, TierOdds,
+    };
+    use crate::state::{AssetAmount, BiggestWin, ClaimConfig, DustDestination, Entry, PrizeTier, Sponsorship, TierPrize, JackpotConfig, JackpotHit, Payout, PendingClaim, GlobalStats, PlayerStats, Raffle, RaffleSummary, UnawardedPrize, UnclaimedPrize};
+
+    const ADMIN: &str = "creator";
+    const PLAYER: &str = "player";
+    const SPONSOR: &str = "sponsor";
+    const NATIVE_PLAYER: &str = "native_player";
+    const BLOCKED: &str = "blocked";
+    const TREASURY: &str = "treasury";
+
+    fn raffle_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        )
+        .with_reply(crate::contract::reply);
+        Box::new(contract)
+    }
+
+    fn cw20_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw20_base::contract::execute,
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        );
+        Box::new(contract)
+    }
+
+    fn cw721_instantiate(deps: DepsMut, env: Env, info: MessageInfo, msg: cw721_base::InstantiateMsg) -> StdResult<Response> {
+        Cw721Contract::<Extension, Empty>::default().instantiate(deps, env, info, msg)
+    }
+
+    fn cw721_execute(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: cw721_base::ExecuteMsg<Extension>,
+    ) -> Result<Response, cw721_base::ContractError> {
+        Cw721Contract::<Extension, Empty>::default().execute(deps, env, info, msg)
+    }
+
+    fn cw721_query(deps: Deps, env: Env, msg: cw721_base::QueryMsg) -> StdResult<Binary> {
+        Cw721Contract::<Extension, Empty>::default().query(deps, env, msg)
+    }
+
+    fn cw721_contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(cw721_execute, cw721_instantiate, cw721_query))
+    }
+
+    // A cw20 refusing every transfer to BLOCKED, like a token with a deny list
+    fn blocking_cw20_contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(
+            |deps, env, info, msg: Cw20ExecuteMsg| {
+                if let Cw20ExecuteMsg::Transfer { recipient, .. } = &msg {
+                    if recipient == BLOCKED {
+                        return Err(cw20_base::ContractError::Unauthorized {});
+                    }
+                }
+                cw20_base::contract::execute(deps, env, info, msg)
+            },
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        ))
+    }
+
+    // A collection whose tokens carry TicketMetadata, as used for ticket_nft
+    fn ticket_contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(
+            |deps, env, info, msg: cw721_base::ExecuteMsg<TicketMetadata>| {
+                Cw721Contract::<TicketMetadata, Empty>::default().execute(deps, env, info, msg)
+            },
+            |deps, env, info, msg| Cw721Contract::<TicketMetadata, Empty>::default().instantiate(deps, env, info, msg),
+            |deps, env, msg| Cw721Contract::<TicketMetadata, Empty>::default().query(deps, env, msg),
+        ))
+    }
+
+    const RECEIVED: Item<Vec<RaffleHookMsg>> = Item::new("received");
+    const FAIL: Item<bool> = Item::new("fail");
+
+    // Records every RaffleHookMsg it gets, or rejects them all when instantiated with fail: true
+    fn hook_contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(
+            |deps, _env, _info, msg: RaffleHookExecuteMsg| -> StdResult<Response> {
+                let RaffleHookExecuteMsg::RaffleHook(hook) = msg;
+                if FAIL.load(deps.storage)? {
+                    return Err(cosmwasm_std::StdError::generic_err("hook failed"));
+                }
+                RECEIVED.update(deps.storage, |mut received| -> StdResult<_> {
+                    received.push(hook);
+                    Ok(received)
+                })?;
+                Ok(Response::new())
+            },
+            |deps, _env, _info, fail: bool| -> StdResult<Response> {
+                FAIL.save(deps.storage, &fail)?;
+                RECEIVED.save(deps.storage, &vec![])?;
+                Ok(Response::new())
+            },
+            |deps, _env, _msg: Empty| -> StdResult<Binary> { to_binary(&RECEIVED.load(deps.storage)?) },
+        ))
+    }
+
+    // Instantiates the raffle contract and a cw20 token funding PLAYER and SPONSOR, NATIVE_PLAYER holds ujuno
+    fn setup() -> (App, Addr, Addr) {
+        let mut app = App::new(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(NATIVE_PLAYER), coins(1_000, "ujuno"))
+                .unwrap();
+        });
+
+        let cw20_id = app.store_code(cw20_contract());
+        let token = app
+            .instantiate_contract(
+                cw20_id,
+                Addr::unchecked(ADMIN),
+                &cw20_base::msg::InstantiateMsg {
+                    name: "Fury Token".to_string(),
+                    symbol: "FURY".to_string(),
+                    decimals: 6,
+                    initial_balances: vec![
+                        Cw20Coin { address: PLAYER.to_string(), amount: Uint128::new(1_000) },
+                        Cw20Coin { address: SPONSOR.to_string(), amount: Uint128::new(1_000) },
+                    ],
+                    mint: None,
+                    marketing: None,
+                },
+                &[],
+                "token",
+                None,
+            )
+            .unwrap();
+
+        let raffle_id = app.store_code(raffle_contract());
+        let raffle = app
+            .instantiate_contract(
+                raffle_id,
+                Addr::unchecked(ADMIN),
+                &InstantiateMsg { admins: vec![ADMIN.to_string()], allowed_denoms: vec![], treasury: TREASURY.to_string() },
+                &[],
+                "raffle",
+                None,
+            )
+            .unwrap();
+
+        (app, raffle, token)
+    }
+
+    fn begin_token_raffle(app: &mut App, raffle: &Addr, token: &Addr, series: Option<String>) {
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                series,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn send_tokens(app: &mut App, sender: &str, token: &Addr, raffle: &Addr, amount: u128, msg: &ReceiveMsg) {
+        app.execute_contract(
+            Addr::unchecked(sender),
+            token.clone(),
+            &Cw20ExecuteMsg::Send {
+                contract: raffle.to_string(),
+                amount: Uint128::new(amount),
+                msg: to_binary(msg).unwrap(),
+            },
+            &[],
+        )
+        .unwrap();
+    }
+
+    fn raffle_info(app: &App, raffle: &Addr, id: u32) -> Raffle {
+        app.wrap()
+            .query_wasm_smart(raffle, &QueryMsg::GetRaffleInfo { id })
+            .unwrap()
+    }
+
+    #[test]
+    fn join_and_buy_tickets_with_cw20() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        send_tokens(&mut app, PLAYER, &token, &raffle, 30, &ReceiveMsg::BuyTickets { id: 0 });
+
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!((info.entrants, info.tickets), (1, 4));
+        let entries: Vec<Entry> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetEntries { id: 0, start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(entries[0].tickets, 4);
+        assert_eq!(entries[0].paid, vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(40) }]);
+        assert_eq!(info.assets[0].pot, Uint128::new(40));
+
+        // joining twice is still refused
+        let err = app
+            .execute_contract(
+                Addr::unchecked(PLAYER),
+                token.clone(),
+                &Cw20ExecuteMsg::Send {
+                    contract: raffle.to_string(),
+                    amount: Uint128::new(10),
+                    msg: to_binary(&ReceiveMsg::JoinRaffleRound { id: 0 }).unwrap(),
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Already Registered");
+
+        // the contract really holds the tokens
+        let balance: cw20::BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(&token, &cw20::Cw20QueryMsg::Balance { address: raffle.to_string() })
+            .unwrap();
+        assert_eq!(balance.balance, Uint128::new(40));
+        let deposit: Uint128 = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetTotalDeposit { denom: Denom::Cw20(token.clone()) })
+            .unwrap();
+        assert_eq!(deposit, Uint128::new(40));
+    }
+
+    #[test]
+    fn sponsor_and_fund_series_with_cw20() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(SPONSOR),
+                token.clone(),
+                &Cw20ExecuteMsg::Send {
+                    contract: raffle.to_string(),
+                    amount: Uint128::new(100),
+                    msg: to_binary(&ReceiveMsg::SponsorRaffle { id: 0 }).unwrap(),
+                },
+                &[],
+            )
+            .unwrap();
+        let sponsored = res.events.iter().find(|event| event.ty == "wasm-raffle_sponsored").unwrap();
+        assert_eq!(sponsored.attributes[1..], [
+            cosmwasm_std::attr("raffle_id", "0"),
+            cosmwasm_std::attr("sponsor", SPONSOR),
+            cosmwasm_std::attr("amount", "100"),
+            cosmwasm_std::attr("denom", token.to_string()),
+        ]);
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.tickets, 0);
+        assert_eq!(info.assets[0].pot, Uint128::new(100));
+
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 50, &ReceiveMsg::FundSeries { series: "weekly".to_string() });
+        begin_token_raffle(&mut app, &raffle, &token, Some("weekly".to_string()));
+        let info = raffle_info(&app, &raffle, 1);
+        assert_eq!(info.series, Some("weekly".to_string()));
+        assert_eq!(info.assets[0].pot, Uint128::new(50));
+
+        // the pool is spent by the raffle it seeded
+        begin_token_raffle(&mut app, &raffle, &token, Some("weekly".to_string()));
+        assert_eq!(raffle_info(&app, &raffle, 2).assets[0].pot, Uint128::zero());
+
+        // cancelling gives it back to the series, for the next raffle to take
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::BuyTickets { id: 1 });
+        let res = app
+            .execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 1 }, &[])
+            .unwrap();
+        let cancelled = res.events.iter().find(|event| event.ty == "wasm-raffle_cancelled").unwrap();
+        assert!(cancelled.attributes.contains(&cosmwasm_std::attr("returned_to_series", format!("50{}", token))));
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(1_000));
+        begin_token_raffle(&mut app, &raffle, &token, Some("weekly".to_string()));
+        assert_eq!(raffle_info(&app, &raffle, 3).assets[0].pot, Uint128::new(50));
+    }
+
+    #[test]
+    fn reject_other_cw20() {
+        let (mut app, raffle, token) = setup();
+
+        let cw20_id = app.store_code(cw20_contract());
+        let other = app
+            .instantiate_contract(
+                cw20_id,
+                Addr::unchecked(ADMIN),
+                &cw20_base::msg::InstantiateMsg {
+                    name: "Other Token".to_string(),
+                    symbol: "OTHER".to_string(),
+                    decimals: 6,
+                    initial_balances: vec![Cw20Coin { address: PLAYER.to_string(), amount: Uint128::new(1_000) }],
+                    mint: None,
+                    marketing: None,
+                },
+                &[],
+                "other",
+                None,
+            )
+            .unwrap();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+
+        let err = app
+            .execute_contract(
+                Addr::unchecked(PLAYER),
+                other,
+                &Cw20ExecuteMsg::Send {
+                    contract: raffle.to_string(),
+                    amount: Uint128::new(10),
+                    msg: to_binary(&ReceiveMsg::JoinRaffleRound { id: 0 }).unwrap(),
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Wrong Payment");
+    }
+
+    #[test]
+    fn pot_per_accepted_asset() {
+        let (mut app, raffle, token) = setup();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![
+                    AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::new(5) },
+                    AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) },
+                ],
+                series: None,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+
+        send_tokens(&mut app, PLAYER, &token, &raffle, 20, &ReceiveMsg::BuyTickets { id: 0 });
+        app.execute_contract(
+            Addr::unchecked(NATIVE_PLAYER),
+            raffle.clone(),
+            &ExecuteMsg::JoinRaffleRound { id: 0 },
+            &coins(17, "ujuno"),
+        )
+        .unwrap();
+
+        // each asset buys tickets at its own price, 17 ujuno buys 3
+        let pot: Vec<AssetAmount> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetPot { id: 0 })
+            .unwrap();
+        assert_eq!(pot, vec![
+            AssetAmount { denom: Denom::Native("ujuno".to_string()), amount: Uint128::new(17) },
+            AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(20) },
+        ]);
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!((info.entrants, info.tickets), (2, 5));
+
+        // the single winner takes each pot in its own asset
+        app.update_block(|block| block.time = block.time.plus_seconds(1800));
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::EndRaffleRound { id: 0 }, &[])
+            .unwrap();
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.winner_payouts, vec![pot]);
+    }
+
+    // Instantiates a cw721 collection and mints token_ids to ADMIN, who sends them to the raffle as prizes
+    fn deposit_nft_prizes(app: &mut App, raffle: &Addr, id: u32, prizes: &[(&str, u32)]) -> Addr {
+        let cw721_id = app.store_code(cw721_contract());
+        let nft = app
+            .instantiate_contract(
+                cw721_id,
+                Addr::unchecked(ADMIN),
+                &cw721_base::InstantiateMsg {
+                    name: "Prizes".to_string(),
+                    symbol: "PRIZE".to_string(),
+                    minter: ADMIN.to_string(),
+                },
+                &[],
+                "nft",
+                None,
+            )
+            .unwrap();
+
+        for (token_id, rank) in prizes {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                nft.clone(),
+                &cw721_base::ExecuteMsg::<Extension>::Mint(MintMsg {
+                    token_id: token_id.to_string(),
+                    owner: ADMIN.to_string(),
+                    token_uri: None,
+                    extension: None,
+                }),
+                &[],
+            )
+            .unwrap();
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                nft.clone(),
+                &cw721_base::ExecuteMsg::<Extension>::SendNft {
+                    contract: raffle.to_string(),
+                    token_id: token_id.to_string(),
+                    msg: to_binary(&ReceiveNftMsg::AddPrize { id, rank: *rank }).unwrap(),
+                },
+                &[],
+            )
+            .unwrap();
+        }
+
+        nft
+    }
+
+    fn nft_owner(app: &App, nft: &Addr, token_id: &str) -> String {
+        let res: OwnerOfResponse = app
+            .wrap()
+            .query_wasm_smart(nft, &Cw721QueryMsg::OwnerOf { token_id: token_id.to_string(), include_expired: None })
+            .unwrap();
+        res.owner
+    }
+
+    fn end_raffle(app: &mut App, raffle: &Addr, id: u32) {
+        app.update_block(|block| block.time = block.time.plus_seconds(1800));
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::EndRaffleRound { id }, &[])
+            .unwrap();
+    }
+
+    #[test]
+    fn nft_prize_goes_to_winner() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        let nft = deposit_nft_prizes(&mut app, &raffle, 0, &[("first", 0)]);
+        assert_eq!(nft_owner(&app, &nft, "first"), raffle.to_string());
+
+        // ranks outside winners_distribution are refused
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            nft.clone(),
+            &cw721_base::ExecuteMsg::<Extension>::Mint(MintMsg {
+                token_id: "second".to_string(),
+                owner: ADMIN.to_string(),
+                token_uri: None,
+                extension: None,
+            }),
+            &[],
+        )
+        .unwrap();
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                nft.clone(),
+                &cw721_base::ExecuteMsg::<Extension>::SendNft {
+                    contract: raffle.to_string(),
+                    token_id: "second".to_string(),
+                    msg: to_binary(&ReceiveNftMsg::AddPrize { id: 0, rank: 1 }).unwrap(),
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "No prize rank 1 in this raffle");
+
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        end_raffle(&mut app, &raffle, 0);
+
+        assert_eq!(nft_owner(&app, &nft, "first"), PLAYER);
+        // the token pot was paid out as well
+        let balance: cw20::BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(&token, &cw20::Cw20QueryMsg::Balance { address: PLAYER.to_string() })
+            .unwrap();
+        assert_eq!(balance.balance, Uint128::new(1_000));
+    }
+
+    #[test]
+    fn nft_prize_returned_without_players() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        let nft = deposit_nft_prizes(&mut app, &raffle, 0, &[("first", 0)]);
+
+        end_raffle(&mut app, &raffle, 0);
+        assert_eq!(nft_owner(&app, &nft, "first"), ADMIN);
+    }
+
+    #[test]
+    fn cancel_refunds_players_and_nfts() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        let nft = deposit_nft_prizes(&mut app, &raffle, 0, &[("first", 0)]);
+        send_tokens(&mut app, PLAYER, &token, &raffle, 30, &ReceiveMsg::BuyTickets { id: 0 });
+
+        let err = app
+            .execute_contract(Addr::unchecked(PLAYER), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Unauthorized");
+
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 0 }, &[])
+            .unwrap();
+
+        assert_eq!(nft_owner(&app, &nft, "first"), ADMIN);
+        let balance: cw20::BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(&token, &cw20::Cw20QueryMsg::Balance { address: PLAYER.to_string() })
+            .unwrap();
+        assert_eq!(balance.balance, Uint128::new(1_000));
+
+        let info = raffle_info(&app, &raffle, 0);
+        assert!(info.cancelled);
+        assert!(!info.active);
+
+        // a cancelled raffle can't be settled
+        app.update_block(|block| block.time = block.time.plus_seconds(1800));
+        let err = app
+            .execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::EndRaffleRound { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Raffle already settled or cancelled");
+    }
+
+    #[test]
+    fn sponsors_are_recorded_and_refunded() {
+        let (mut app, raffle, token) = setup();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![
+                    AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::new(5) },
+                    AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) },
+                ],
+                series: None,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+
+        let err = app
+            .execute_contract(Addr::unchecked(NATIVE_PLAYER), raffle.clone(), &ExecuteMsg::SponsorRaffle { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Wrong Payment");
+        app.execute_contract(Addr::unchecked(NATIVE_PLAYER), raffle.clone(), &ExecuteMsg::SponsorRaffle { id: 0 }, &coins(300, "ujuno"))
+            .unwrap();
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 50, &ReceiveMsg::SponsorRaffle { id: 0 });
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 25, &ReceiveMsg::SponsorRaffle { id: 0 });
+
+        // sponsors add to the pot without playing, each sponsor's amounts add up
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.tickets, 0);
+        assert_eq!(info.sponsors, 2);
+        assert_eq!(info.assets[0].pot, Uint128::new(300));
+        assert_eq!(info.assets[1].pot, Uint128::new(75));
+        let sponsorships: Vec<Sponsorship> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetSponsorships { id: 0, start_after: None, limit: Some(1) })
+            .unwrap();
+        assert_eq!(sponsorships, vec![Sponsorship {
+            raffle_id: 0,
+            sponsor: Addr::unchecked(NATIVE_PLAYER),
+            amounts: vec![AssetAmount { denom: Denom::Native("ujuno".to_string()), amount: Uint128::new(300) }],
+        }]);
+        let sponsorships: Vec<Sponsorship> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetSponsorships { id: 0, start_after: Some(NATIVE_PLAYER.to_string()), limit: None })
+            .unwrap();
+        assert_eq!(sponsorships, vec![Sponsorship {
+            raffle_id: 0,
+            sponsor: Addr::unchecked(SPONSOR),
+            amounts: vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(75) }],
+        }]);
+
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 0 }, &[])
+            .unwrap();
+        assert_eq!(app.wrap().query_balance(NATIVE_PLAYER, "ujuno").unwrap().amount, Uint128::new(1_000));
+        assert_eq!(token_balance(&app, &token, SPONSOR), Uint128::new(1_000));
+    }
+
+    #[test]
+    fn ticket_nft_owner_wins() {
+        let (mut app, raffle, token) = setup();
+
+        let tickets_id = app.store_code(ticket_contract());
+        let tickets = app
+            .instantiate_contract(
+                tickets_id,
+                Addr::unchecked(ADMIN),
+                &cw721_base::InstantiateMsg {
+                    name: "Tickets".to_string(),
+                    symbol: "TICKET".to_string(),
+                    minter: raffle.to_string(),
+                },
+                &[],
+                "tickets",
+                None,
+            )
+            .unwrap();
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                series: None,
+                ticket_nft: Some(tickets.to_string()),
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+
+        send_tokens(&mut app, PLAYER, &token, &raffle, 20, &ReceiveMsg::BuyTickets { id: 0 });
+        assert_eq!(nft_owner(&app, &tickets, "0-0"), PLAYER);
+        assert_eq!(nft_owner(&app, &tickets, "0-1"), PLAYER);
+        let nft_info: NftInfoResponse<TicketMetadata> = app
+            .wrap()
+            .query_wasm_smart(&tickets, &Cw721QueryMsg::NftInfo { token_id: "0-1".to_string() })
+            .unwrap();
+        assert_eq!(nft_info.extension, TicketMetadata { raffle_id: 0, ticket_number: 1 });
+
+        // the player sells both tickets before the draw
+        for token_id in ["0-0", "0-1"] {
+            app.execute_contract(
+                Addr::unchecked(PLAYER),
+                tickets.clone(),
+                &cw721_base::ExecuteMsg::<TicketMetadata>::TransferNft {
+                    recipient: SPONSOR.to_string(),
+                    token_id: token_id.to_string(),
+                },
+                &[],
+            )
+            .unwrap();
+        }
+
+        end_raffle(&mut app, &raffle, 0);
+        assert_eq!(raffle_info(&app, &raffle, 0).winners, vec![SPONSOR.to_string()]);
+        let balance: cw20::BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(&token, &cw20::Cw20QueryMsg::Balance { address: SPONSOR.to_string() })
+            .unwrap();
+        assert_eq!(balance.balance, Uint128::new(1_020));
+    }
+
+    #[test]
+    fn burned_ticket_falls_back_to_buyer() {
+        let (mut app, raffle, token) = setup();
+
+        let tickets_id = app.store_code(ticket_contract());
+        let tickets = app
+            .instantiate_contract(
+                tickets_id,
+                Addr::unchecked(ADMIN),
+                &cw721_base::InstantiateMsg {
+                    name: "Tickets".to_string(),
+                    symbol: "TICKET".to_string(),
+                    minter: raffle.to_string(),
+                },
+                &[],
+                "tickets",
+                None,
+            )
+            .unwrap();
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                series: None,
+                ticket_nft: Some(tickets.to_string()),
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::BuyTickets { id: 0 });
+        app.execute_contract(
+            Addr::unchecked(PLAYER),
+            tickets.clone(),
+            &cw721_base::ExecuteMsg::<TicketMetadata>::Burn { token_id: "0-0".to_string() },
+            &[],
+        )
+        .unwrap();
+
+        end_raffle(&mut app, &raffle, 0);
+        assert_eq!(raffle_info(&app, &raffle, 0).winners, vec![PLAYER.to_string()]);
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(1_000));
+    }
+
+    #[test]
+    fn hooks_receive_lifecycle_events() {
+        let (mut app, raffle, token) = setup();
+
+        let hook_id = app.store_code(hook_contract());
+        let listener = app
+            .instantiate_contract(hook_id, Addr::unchecked(ADMIN), &false, &[], "listener", None)
+            .unwrap();
+        let failing = app
+            .instantiate_contract(hook_id, Addr::unchecked(ADMIN), &true, &[], "failing", None)
+            .unwrap();
+
+        let err = app
+            .execute_contract(Addr::unchecked(PLAYER), raffle.clone(), &ExecuteMsg::AddHook { addr: listener.to_string() }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Unauthorized");
+        for hook in [&listener, &failing] {
+            app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::AddHook { addr: hook.to_string() }, &[])
+                .unwrap();
+        }
+        let hooks: HooksResponse = app.wrap().query_wasm_smart(&raffle, &QueryMsg::GetHooks {}).unwrap();
+        assert_eq!(hooks.hooks, vec![listener.to_string(), failing.to_string()]);
+
+        // the failing hook blocks none of these
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        send_tokens(&mut app, PLAYER, &token, &raffle, 20, &ReceiveMsg::BuyTickets { id: 0 });
+        end_raffle(&mut app, &raffle, 0);
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 1 }, &[])
+            .unwrap();
+
+        let received: Vec<RaffleHookMsg> = app.wrap().query_wasm_smart(&listener, &Empty {}).unwrap();
+        assert_eq!(received, vec![
+            RaffleHookMsg::Opened { id: 0 },
+            RaffleHookMsg::Entered { id: 0, player: PLAYER.to_string(), tickets: 2 },
+            RaffleHookMsg::Settled { id: 0, winners: vec![PLAYER.to_string()] },
+            RaffleHookMsg::Opened { id: 1 },
+            RaffleHookMsg::Cancelled { id: 1 },
+        ]);
+        let received: Vec<RaffleHookMsg> = app.wrap().query_wasm_smart(&failing, &Empty {}).unwrap();
+        assert!(received.is_empty());
+
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::RemoveHook { addr: listener.to_string() }, &[])
+            .unwrap();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        let received: Vec<RaffleHookMsg> = app.wrap().query_wasm_smart(&listener, &Empty {}).unwrap();
+        assert_eq!(received.len(), 5);
+    }
+
+    #[test]
+    fn failed_payout_is_recorded_and_retried() {
+        let (mut app, raffle, _) = setup();
+
+        let cw20_id = app.store_code(blocking_cw20_contract());
+        let token = app
+            .instantiate_contract(
+                cw20_id,
+                Addr::unchecked(ADMIN),
+                &cw20_base::msg::InstantiateMsg {
+                    name: "Blocking Token".to_string(),
+                    symbol: "BLOCK".to_string(),
+                    decimals: 6,
+                    initial_balances: vec![Cw20Coin { address: BLOCKED.to_string(), amount: Uint128::new(1_000) }],
+                    mint: None,
+                    marketing: None,
+                },
+                &[],
+                "blocking",
+                None,
+            )
+            .unwrap();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        send_tokens(&mut app, BLOCKED, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+
+        // the draw goes through although the winner can't be paid
+        end_raffle(&mut app, &raffle, 0);
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.winners, vec![BLOCKED.to_string()]);
+        assert!(!info.active);
+
+        let failed: Vec<Payout> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetFailedPayouts { raffle_id: Some(0), start_after: None, limit: None })
+            .unwrap();
+        let payout = Payout {
+            id: 1,
+            raffle_id: 0,
+            recipient: BLOCKED.to_string(),
+            denom: Denom::Cw20(token.clone()),
+            amount: Uint128::new(10),
+            winner: Some(BLOCKED.to_string()),
+        };
+        assert_eq!(failed, vec![payout.clone()]);
+        // nothing is credited as won before it arrives
+        assert_eq!(player_stats(&app, &raffle, BLOCKED).wins, 1);
+        assert!(player_stats(&app, &raffle, BLOCKED).won.is_empty());
+
+        // retrying to the same address fails again and keeps the payout
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::RetryPayout { payout_id: 1, recipient: None }, &[])
+            .unwrap();
+        let failed: Vec<Payout> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetFailedPayouts { raffle_id: None, start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(failed, vec![payout]);
+
+        // only the recipient may redirect it
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                raffle.clone(),
+                &ExecuteMsg::RetryPayout { payout_id: 1, recipient: Some(PLAYER.to_string()) },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Unauthorized");
+        app.execute_contract(
+            Addr::unchecked(BLOCKED),
+            raffle.clone(),
+            &ExecuteMsg::RetryPayout { payout_id: 1, recipient: Some(PLAYER.to_string()) },
+            &[],
+        )
+        .unwrap();
+
+        let balance: cw20::BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(&token, &cw20::Cw20QueryMsg::Balance { address: PLAYER.to_string() })
+            .unwrap();
+        assert_eq!(balance.balance, Uint128::new(10));
+        let failed: Vec<Payout> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetFailedPayouts { raffle_id: None, start_after: None, limit: None })
+            .unwrap();
+        assert!(failed.is_empty());
+        // the prize is the winner's, wherever they had it sent
+        let won = vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(10) }];
+        assert_eq!(player_stats(&app, &raffle, BLOCKED).won, won);
+        let stats: GlobalStats = app.wrap().query_wasm_smart(&raffle, &QueryMsg::GlobalStats {}).unwrap();
+        assert_eq!(stats.paid_out, won);
+
+        let err = app
+            .execute_contract(Addr::unchecked(BLOCKED), raffle.clone(), &ExecuteMsg::RetryPayout { payout_id: 1, recipient: None }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "No failed payout with id 1");
+    }
+
+    // A cw20 raffle in claim mode with a one hour claim period, joined by each of players and then drawn
+    fn won_claim_raffle(app: &mut App, raffle: &Addr, token: &Addr, unclaimed: UnclaimedPrize, series: Option<String>, players: &[&str]) {
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                series,
+                ticket_nft: None,
+                claim: Some(ClaimConfig { period: 3600, unclaimed }),
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        for player in players {
+            send_tokens(app, player, token, raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        }
+        end_raffle(app, raffle, 0);
+    }
+
+    fn token_balance(app: &App, token: &Addr, address: &str) -> Uint128 {
+        let balance: cw20::BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(token, &cw20::Cw20QueryMsg::Balance { address: address.to_string() })
+            .unwrap();
+        balance.balance
+    }
+
+    #[test]
+    fn winner_claims_prize() {
+        let (mut app, raffle, token) = setup();
+        won_claim_raffle(&mut app, &raffle, &token, UnclaimedPrize::Treasury, None, &[PLAYER]);
+
+        // nothing is paid at the draw, nor counted as won
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(990));
+        assert_eq!(player_stats(&app, &raffle, PLAYER).wins, 0);
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.winners, vec![PLAYER.to_string()]);
+        assert_eq!(info.pending_claims, vec![PendingClaim { rank: 0, deadline: app.block_info().time.plus_seconds(3600) }]);
+
+        let err = app
+            .execute_contract(Addr::unchecked(SPONSOR), raffle.clone(), &ExecuteMsg::ClaimPrize { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Nothing to claim");
+        let err = app
+            .execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::ExpireClaims { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "No claim has expired");
+
+        app.execute_contract(Addr::unchecked(PLAYER), raffle.clone(), &ExecuteMsg::ClaimPrize { id: 0 }, &[])
+            .unwrap();
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(1_000));
+        assert!(raffle_info(&app, &raffle, 0).pending_claims.is_empty());
+        let stats = player_stats(&app, &raffle, PLAYER);
+        assert_eq!((stats.wins, stats.won[0].amount), (1, Uint128::new(10)));
+
+        let err = app
+            .execute_contract(Addr::unchecked(PLAYER), raffle.clone(), &ExecuteMsg::ClaimPrize { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Nothing to claim");
+    }
+
+    #[test]
+    fn unclaimed_prize_goes_to_treasury() {
+        let (mut app, raffle, token) = setup();
+        won_claim_raffle(&mut app, &raffle, &token, UnclaimedPrize::Treasury, None, &[PLAYER]);
+
+        app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        let err = app
+            .execute_contract(Addr::unchecked(PLAYER), raffle.clone(), &ExecuteMsg::ClaimPrize { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Claim period is over");
+
+        let err = app
+            .execute_contract(Addr::unchecked(PLAYER), raffle.clone(), &ExecuteMsg::ExpireClaims { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Unauthorized");
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::ExpireClaims { id: 0 }, &[])
+            .unwrap();
+        assert_eq!(token_balance(&app, &token, TREASURY), Uint128::new(10));
+        assert!(raffle_info(&app, &raffle, 0).pending_claims.is_empty());
+    }
+
+    #[test]
+    fn unclaimed_prize_rolls_over_into_series() {
+        let (mut app, raffle, token) = setup();
+        won_claim_raffle(&mut app, &raffle, &token, UnclaimedPrize::Rollover, Some("weekly".to_string()), &[PLAYER]);
+
+        app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::ExpireClaims { id: 0 }, &[])
+            .unwrap();
+
+        begin_token_raffle(&mut app, &raffle, &token, Some("weekly".to_string()));
+        assert_eq!(raffle_info(&app, &raffle, 1).assets[0].pot, Uint128::new(10));
+    }
+
+    #[test]
+    fn unclaimed_prize_is_redrawn() {
+        let (mut app, raffle, token) = setup();
+        won_claim_raffle(&mut app, &raffle, &token, UnclaimedPrize::Redraw, None, &[PLAYER, SPONSOR]);
+        let first = raffle_info(&app, &raffle, 0).winners[0].clone();
+        let other = if first == PLAYER { SPONSOR } else { PLAYER };
+
+        let err = app
+            .execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::Redraw { id: 0, rank: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Claim of rank 0 hasn't expired");
+
+        // the first winner forfeits and the only other player is drawn
+        app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::Redraw { id: 0, rank: 0 }, &[])
+            .unwrap();
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.winners, vec![other.to_string()]);
+        assert_eq!(info.pending_claims, vec![PendingClaim { rank: 0, deadline: app.block_info().time.plus_seconds(3600) }]);
+        assert_eq!(info.redraws.len(), 1);
+        assert_eq!(info.redraws[0].forfeited, first);
+        assert_eq!(info.redraws[0].winner, Some(other.to_string()));
+
+        let err = app
+            .execute_contract(Addr::unchecked(first.as_str()), raffle.clone(), &ExecuteMsg::ClaimPrize { id: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Nothing to claim");
+
+        // once both have forfeited nobody is left and the prize goes to the treasury
+        app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::ExpireClaims { id: 0 }, &[])
+            .unwrap();
+        let info = raffle_info(&app, &raffle, 0);
+        assert!(info.pending_claims.is_empty());
+        assert_eq!(info.redraws.len(), 2);
+        assert_eq!(info.redraws[1].forfeited, other);
+        assert_eq!(info.redraws[1].winner, None);
+        assert_eq!(token_balance(&app, &token, TREASURY), Uint128::new(20));
+    }
+
+    #[test]
+    fn redraw_finds_few_eligible_tickets() {
+        let (mut app, raffle, token) = setup();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(1) }],
+                series: None,
+                ticket_nft: None,
+                claim: Some(ClaimConfig { period: 3600, unclaimed: UnclaimedPrize::Redraw }),
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        send_tokens(&mut app, PLAYER, &token, &raffle, 999, &ReceiveMsg::BuyTickets { id: 0 });
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 1, &ReceiveMsg::BuyTickets { id: 0 });
+        end_raffle(&mut app, &raffle, 0);
+        assert_eq!(raffle_info(&app, &raffle, 0).winners, vec![PLAYER.to_string()]);
+
+        // sampling all but surely hits the forfeited player's tickets, the other player still wins
+        app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::Redraw { id: 0, rank: 0 }, &[])
+            .unwrap();
+        assert_eq!(raffle_info(&app, &raffle, 0).winners, vec![SPONSOR.to_string()]);
+    }
+
+    #[test]
+    fn redraw_needs_redraw_config() {
+        let (mut app, raffle, token) = setup();
+        won_claim_raffle(&mut app, &raffle, &token, UnclaimedPrize::Treasury, None, &[PLAYER]);
+
+        app.update_block(|block| block.time = block.time.plus_seconds(3600));
+        let err = app
+            .execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::Redraw { id: 0, rank: 0 }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Raffle doesn't redraw unclaimed prizes");
+    }
+
+    #[test]
+    fn rollover_needs_series() {
+        let (mut app, raffle, token) = setup();
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                raffle.clone(),
+                &ExecuteMsg::BeginRaffleRound {
+                    expire_type: 0,
+                    winners_distribution: vec![1],
+                    prize_tiers: vec![],
+                    accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token), ticket_price: Uint128::new(10) }],
+                    series: None,
+                    ticket_nft: None,
+                    claim: Some(ClaimConfig { period: 3600, unclaimed: UnclaimedPrize::Rollover }),
+                    unawarded: UnawardedPrize::ProRata,
+                    dust: DustDestination::FirstWinner,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Claim period must be positive, rollover needs a series");
+    }
+
+    #[test]
+    fn series_jackpot_grows_and_is_won() {
+        let (mut app, raffle, token) = setup();
+        let weekly = "weekly".to_string();
+        let set_jackpot = |app: &mut App, odds: u32| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                raffle.clone(),
+                &ExecuteMsg::UpdateJackpot { series: "weekly".to_string(), config: Some(JackpotConfig { share_bps: 1_000, odds }) },
+                &[],
+            )
+        };
+        let jackpot = |app: &App| -> Vec<AssetAmount> {
+            app.wrap().query_wasm_smart(&raffle, &QueryMsg::GetJackpot { series: "weekly".to_string() }).unwrap()
+        };
+
+        let err = set_jackpot(&mut app, 0).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Jackpot share must be at most 10000 bps and odds positive");
+
+        // practically never hit: 10% of the pot stays in the jackpot
+        set_jackpot(&mut app, u32::MAX).unwrap();
+        begin_token_raffle(&mut app, &raffle, &token, Some(weekly.clone()));
+        send_tokens(&mut app, PLAYER, &token, &raffle, 100, &ReceiveMsg::BuyTickets { id: 0 });
+        end_raffle(&mut app, &raffle, 0);
+        let info = raffle_info(&app, &raffle, 0);
+        let ten = vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(10) }];
+        assert_eq!(info.jackpot_contributions, ten);
+        assert_eq!(info.winner_payouts[0][0].amount, Uint128::new(90));
+        assert_eq!(jackpot(&app), ten);
+
+        // always hit: the first winner takes the whole jackpot on top of the pot
+        set_jackpot(&mut app, 1).unwrap();
+        begin_token_raffle(&mut app, &raffle, &token, Some(weekly.clone()));
+        send_tokens(&mut app, PLAYER, &token, &raffle, 100, &ReceiveMsg::BuyTickets { id: 1 });
+        end_raffle(&mut app, &raffle, 1);
+        assert_eq!(raffle_info(&app, &raffle, 1).winner_payouts[0][0].amount, Uint128::new(110));
+        assert!(jackpot(&app).is_empty());
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(1_000));
+
+        let hits: Vec<JackpotHit> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetJackpotHits { series: weekly, start_after: None, limit: None })
+            .unwrap();
+        assert_eq!(hits, vec![JackpotHit {
+            raffle_id: 1,
+            winner: PLAYER.to_string(),
+            amounts: vec![AssetAmount { denom: Denom::Cw20(token), amount: Uint128::new(20) }],
+            time: app.block_info().time,
+        }]);
+    }
+
+    // Begins a raffle with three ranks and lets PLAYER, alone, buy a single 100 token ticket
+    fn unawarded_raffle(app: &mut App, raffle: &Addr, token: &Addr, unawarded: UnawardedPrize) {
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![2, 1, 1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(100) }],
+                series: Some("weekly".to_string()),
+                ticket_nft: None,
+                claim: None,
+                unawarded,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        send_tokens(app, PLAYER, token, raffle, 100, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        end_raffle(app, raffle, 0);
+    }
+
+    #[test]
+    fn unawarded_shares_go_where_configured() {
+        // with a single entry only the first rank is awarded
+        let (mut app, raffle, token) = setup();
+        unawarded_raffle(&mut app, &raffle, &token, UnawardedPrize::ProRata);
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.winner_payouts[0][0].amount, Uint128::new(100));
+        assert!(info.unawarded_amounts.is_empty());
+
+        let (mut app, raffle, token) = setup();
+        unawarded_raffle(&mut app, &raffle, &token, UnawardedPrize::Treasury);
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.winner_payouts[0][0].amount, Uint128::new(50));
+        assert_eq!(info.unawarded_amounts, vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(50) }]);
+        assert_eq!(token_balance(&app, &token, TREASURY), Uint128::new(50));
+
+        let (mut app, raffle, token) = setup();
+        unawarded_raffle(&mut app, &raffle, &token, UnawardedPrize::NextRound);
+        begin_token_raffle(&mut app, &raffle, &token, Some("weekly".to_string()));
+        assert_eq!(raffle_info(&app, &raffle, 1).assets[0].pot, Uint128::new(50));
+    }
+
+    #[test]
+    fn fixed_and_shared_prize_tiers() {
+        let (mut app, raffle, token) = setup();
+        let begin = |app: &mut App, winners_distribution: Vec<u32>, prize_tiers: Vec<PrizeTier>| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                raffle.clone(),
+                &ExecuteMsg::BeginRaffleRound {
+                    expire_type: 0,
+                    winners_distribution,
+                    prize_tiers,
+                    accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                    series: None,
+                    ticket_nft: None,
+                    claim: None,
+                    unawarded: UnawardedPrize::ProRata,
+                    dust: DustDestination::FirstWinner,
+                },
+                &[],
+            )
+        };
+        let fixed = |denom: Denom, amount: u128| PrizeTier {
+            winners: 1,
+            prize: TierPrize::Fixed { amounts: vec![AssetAmount { denom, amount: Uint128::new(amount) }] },
+        };
+        let invalid = "Prize tiers need winners, shares that add up to a u32, at most 10000 bps in all and fixed amounts in accepted assets";
+
+        let err = begin(&mut app, vec![1], vec![fixed(Denom::Cw20(token.clone()), 100)]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), invalid);
+        let err = begin(&mut app, vec![], vec![fixed(Denom::Native("ujuno".to_string()), 100)]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), invalid);
+        let err = begin(&mut app, vec![], vec![PrizeTier { winners: 2, prize: TierPrize::Percent { bps: 10_001 } }]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), invalid);
+        let err = begin(&mut app, vec![u32::MAX, 1], vec![]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), invalid);
+        let err = begin(&mut app, vec![], vec![PrizeTier { winners: u32::MAX, prize: TierPrize::Shares { shares: 1 } }]).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "A raffle can have at most 1000 winners");
+        // without a share tier nothing would take up the prizes nobody won
+        let err = begin(&mut app, vec![], vec![
+            fixed(Denom::Cw20(token.clone()), 100),
+            PrizeTier { winners: 2, prize: TierPrize::Percent { bps: 5_000 } },
+        ])
+        .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Pro rata unawarded prizes need a share tier to grow");
+
+        // 1st: 100 tokens, 2nd and 3rd: a share each of the rest
+        begin(&mut app, vec![], vec![
+            fixed(Denom::Cw20(token.clone()), 100),
+            PrizeTier { winners: 2, prize: TierPrize::Shares { shares: 1 } },
+        ])
+        .unwrap();
+        send_tokens(&mut app, PLAYER, &token, &raffle, 300, &ReceiveMsg::BuyTickets { id: 0 });
+        end_raffle(&mut app, &raffle, 0);
+
+        let payouts: Vec<Uint128> = raffle_info(&app, &raffle, 0).winner_payouts.iter().map(|payout| payout[0].amount).collect();
+        assert_eq!(payouts, vec![Uint128::new(100), Uint128::new(100), Uint128::new(100)]);
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(1_000));
+    }
+
+    #[test]
+    fn dust_goes_where_configured() {
+        let run = |dust: DustDestination| -> (App, Addr, Addr) {
+            let (mut app, raffle, token) = setup();
+            // 5 of the 100 tokens go to the jackpot and 95 split in three leaves 2 over
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                raffle.clone(),
+                &ExecuteMsg::UpdateJackpot { series: "weekly".to_string(), config: Some(JackpotConfig { share_bps: 500, odds: u32::MAX }) },
+                &[],
+            )
+            .unwrap();
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                raffle.clone(),
+                &ExecuteMsg::BeginRaffleRound {
+                    expire_type: 0,
+                    winners_distribution: vec![1, 1, 1],
+                    prize_tiers: vec![],
+                    accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                    series: Some("weekly".to_string()),
+                    ticket_nft: None,
+                    claim: None,
+                    unawarded: UnawardedPrize::ProRata,
+                    dust,
+                },
+                &[],
+            )
+            .unwrap();
+            send_tokens(&mut app, PLAYER, &token, &raffle, 100, &ReceiveMsg::BuyTickets { id: 0 });
+            end_raffle(&mut app, &raffle, 0);
+
+            // whatever the destination, nothing of the pot is left unaccounted for
+            let info = raffle_info(&app, &raffle, 0);
+            let paid = info.winner_payouts.iter().map(|payout| payout[0].amount).sum::<Uint128>();
+            let dust = match info.dust {
+                DustDestination::FirstWinner => Uint128::zero(),
+                _ => info.dust_amounts[0].amount,
+            };
+            assert_eq!(paid + info.jackpot_contributions[0].amount + dust, info.assets[0].pot);
+            (app, raffle, token)
+        };
+
+        let (app, raffle, _) = run(DustDestination::FirstWinner);
+        let info = raffle_info(&app, &raffle, 0);
+        let payouts: Vec<Uint128> = info.winner_payouts.iter().map(|payout| payout[0].amount).collect();
+        assert_eq!(payouts, vec![Uint128::new(33), Uint128::new(31), Uint128::new(31)]);
+
+        let (app, _, token) = run(DustDestination::Treasury);
+        assert_eq!(token_balance(&app, &token, TREASURY), Uint128::new(2));
+
+        let (mut app, raffle, token) = run(DustDestination::NextRound);
+        begin_token_raffle(&mut app, &raffle, &token, Some("weekly".to_string()));
+        assert_eq!(raffle_info(&app, &raffle, 1).assets[0].pot, Uint128::new(2));
+
+        // with no first winner the dust goes with the unawarded prizes, and only there
+        let (mut app, raffle, token) = setup();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1, 1, 1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                series: None,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::Treasury,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 100, &ReceiveMsg::SponsorRaffle { id: 0 });
+        end_raffle(&mut app, &raffle, 0);
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.unawarded_amounts, vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(100) }]);
+        assert!(info.dust_amounts.is_empty());
+        assert_eq!(token_balance(&app, &token, TREASURY), Uint128::new(100));
+    }
+
+    fn continue_settlement(app: &mut App, raffle: &Addr, limit: Option<u32>) -> String {
+        let res = app
+            .execute_contract(Addr::unchecked(SPONSOR), raffle.clone(), &ExecuteMsg::ContinueSettlement { id: 0, limit }, &[])
+            .unwrap();
+        let event = res.events.iter().find(|event| event.ty == "wasm-settlement_continued").unwrap();
+        event.attributes.iter().find(|attr| attr.key == "remaining").unwrap().value.clone()
+    }
+
+    #[test]
+    fn many_winners_are_paid_in_batches() {
+        let (mut app, raffle, token) = setup();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![],
+                prize_tiers: vec![PrizeTier { winners: 60, prize: TierPrize::Shares { shares: 1 } }],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                series: None,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        send_tokens(&mut app, PLAYER, &token, &raffle, 600, &ReceiveMsg::BuyTickets { id: 0 });
+        end_raffle(&mut app, &raffle, 0);
+
+        // all 60 winners are drawn at once, the first 50 paid
+        let info = raffle_info(&app, &raffle, 0);
+        assert_eq!(info.winners.len(), 60);
+        assert_eq!(info.settlement.unwrap().remaining(), 10);
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(900));
+
+        // anyone can pay the rest, in batches of their choosing
+        assert_eq!(continue_settlement(&mut app, &raffle, Some(4)), "6");
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(940));
+        assert_eq!(continue_settlement(&mut app, &raffle, None), "0");
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(1_000));
+        assert_eq!(raffle_info(&app, &raffle, 0).settlement, None);
+
+        let err = app
+            .execute_contract(Addr::unchecked(SPONSOR), raffle.clone(), &ExecuteMsg::ContinueSettlement { id: 0, limit: None }, &[])
+            .unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Raffle has nothing left to settle");
+    }
+
+    #[test]
+    fn cancel_refunds_in_batches() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        let sponsors: Vec<String> = (0..55).map(|i| format!("sponsor{:02}", i)).collect();
+        for sponsor in sponsors.iter() {
+            app.execute_contract(
+                Addr::unchecked(SPONSOR),
+                token.clone(),
+                &Cw20ExecuteMsg::Transfer { recipient: sponsor.clone(), amount: Uint128::new(1) },
+                &[],
+            )
+            .unwrap();
+            send_tokens(&mut app, sponsor, &token, &raffle, 1, &ReceiveMsg::SponsorRaffle { id: 0 });
+        }
+
+        // the player is refunded first, then the sponsors by address
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 0 }, &[])
+            .unwrap();
+        assert_eq!(token_balance(&app, &token, PLAYER), Uint128::new(1_000));
+        assert_eq!(token_balance(&app, &token, &sponsors[48]), Uint128::new(1));
+        assert_eq!(token_balance(&app, &token, &sponsors[49]), Uint128::zero());
+        assert_eq!(raffle_info(&app, &raffle, 0).settlement.unwrap().remaining(), 6);
+
+        assert_eq!(continue_settlement(&mut app, &raffle, Some(500)), "0");
+        for sponsor in sponsors.iter() {
+            assert_eq!(token_balance(&app, &token, sponsor), Uint128::new(1));
+        }
+        assert_eq!(token_balance(&app, &token, raffle.as_str()), Uint128::zero());
+    }
+
+    #[test]
+    fn settled_raffle_is_archived() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 20, &ReceiveMsg::BuyTickets { id: 0 });
+        let archive = |app: &mut App, limit: Option<u32>| {
+            app.execute_contract(Addr::unchecked(PLAYER), raffle.clone(), &ExecuteMsg::ArchiveRaffle { id: 0, limit }, &[])
+        };
+
+        let err = archive(&mut app, None).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Raffle can only be archived 30 days after settling, with nothing left to pay or claim");
+        end_raffle(&mut app, &raffle, 0);
+        let settled = raffle_info(&app, &raffle, 0);
+        let err = archive(&mut app, None).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Raffle can only be archived 30 days after settling, with nothing left to pay or claim");
+
+        let history = |app: &App| -> Vec<Vec<PlayerRaffle>> {
+            [PLAYER, SPONSOR]
+                .iter()
+                .map(|player| {
+                    app.wrap()
+                        .query_wasm_smart(&raffle, &QueryMsg::PlayerRaffles { address: player.to_string(), start_after: None, limit: None })
+                        .unwrap()
+                })
+                .collect()
+        };
+        let settled_history = history(&app);
+        assert_eq!(settled_history[1][0].tickets, 2);
+
+        // the summary replaces the raffle at once, its two entries and ticket ranges go one per call
+        app.update_block(|block| block.time = block.time.plus_seconds(30 * 24 * 3600));
+        archive(&mut app, Some(1)).unwrap();
+        // players' history holds while pruning, whether their entry is pruned yet or not
+        assert_eq!(history(&app), settled_history);
+        let summary: RaffleSummary = app.wrap().query_wasm_smart(&raffle, &QueryMsg::GetRaffleSummary { id: 0 }).unwrap();
+        assert_eq!(summary.winners, settled.winners);
+        assert_eq!(summary.winner_payouts, settled.winner_payouts);
+        assert_eq!(summary.pot, vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(30) }]);
+        assert_eq!((summary.entrants, summary.tickets), (2, 3));
+        assert_eq!(summary.seed_hash.as_ref().map(String::len), Some(64));
+        assert!(summary.pruning);
+        assert!(app.wrap().query_wasm_smart::<Raffle>(&raffle, &QueryMsg::GetRaffleInfo { id: 0 }).is_err());
+
+        archive(&mut app, Some(1)).unwrap();
+        let res = archive(&mut app, Some(1)).unwrap();
+        let wasm = res.events.iter().find(|event| event.ty == "wasm").unwrap();
+        assert!(wasm.attributes.contains(&cosmwasm_std::attr("pruning", "false")));
+        let entries: Vec<Entry> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::GetEntries { id: 0, start_after: None, limit: None })
+            .unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(history(&app), settled_history);
+
+        let err = archive(&mut app, None).unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "Raffle is already archived");
+    }
+
+    #[test]
+    fn list_raffles_by_status_asset_and_creator() {
+        let (mut app, raffle, token) = setup();
+        for _ in 0..12 {
+            begin_token_raffle(&mut app, &raffle, &token, None);
+        }
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 1,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::new(5) }],
+                series: None,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 3 }, &[])
+            .unwrap();
+        end_raffle(&mut app, &raffle, 0);
+
+        let page = |app: &App, start_after: Option<u32>, status: Option<RaffleStatus>, pay_token: Option<Denom>, creator: Option<&str>, order: Option<ListOrder>| {
+            let page: ListRafflesResponse = app
+                .wrap()
+                .query_wasm_smart(&raffle, &QueryMsg::ListRaffles {
+                    start_after,
+                    limit: None,
+                    status,
+                    pay_token,
+                    creator: creator.map(String::from),
+                    order,
+                })
+                .unwrap();
+            (page.raffles.iter().map(|item| item.id).collect::<Vec<_>>(), page.next_start_after)
+        };
+        let list = |app: &App, start_after, status, pay_token, creator, order| page(app, start_after, status, pay_token, creator, order).0;
+
+        // ids come in numeric order, 10 to a page by default
+        assert_eq!(list(&app, None, None, None, None, None), (0..10).collect::<Vec<_>>());
+        assert_eq!(list(&app, Some(9), None, None, None, None), vec![10, 11, 12]);
+        assert_eq!(list(&app, Some(2), None, None, None, Some(ListOrder::Descending)), vec![1, 0]);
+
+        assert_eq!(list(&app, None, Some(RaffleStatus::Settled), None, None, None), vec![0]);
+        assert_eq!(list(&app, None, Some(RaffleStatus::Cancelled), None, None, None), vec![3]);
+        assert_eq!(list(&app, None, Some(RaffleStatus::Open), None, None, None), vec![12]);
+        assert_eq!(list(&app, Some(8), Some(RaffleStatus::Ended), None, None, None), vec![9, 10, 11]);
+        assert_eq!(list(&app, None, None, Some(Denom::Native("ujuno".to_string())), None, None), vec![12]);
+        assert!(list(&app, None, None, None, Some(PLAYER), None).is_empty());
+        assert_eq!(list(&app, Some(10), None, None, Some(ADMIN), None), vec![11, 12]);
+
+        let items: ListRafflesResponse = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::ListRaffles { start_after: None, limit: Some(1), status: None, pay_token: None, creator: None, order: None })
+            .unwrap();
+        assert_eq!(items.raffles, vec![RaffleListItem {
+            id: 0,
+            status: RaffleStatus::Settled,
+            begin_time_stamp: items.raffles[0].begin_time_stamp,
+            end_time_stamp: items.raffles[0].end_time_stamp,
+            pot: vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(10) }],
+            entrants: 1,
+        }]);
+        assert_eq!(items.next_start_after, Some(0));
+        assert_eq!(page(&app, None, Some(RaffleStatus::Settled), None, None, None), (vec![0], None));
+
+        // a selective filter reads 100 raffles a page and tells where to go on
+        for _ in 0..100 {
+            begin_token_raffle(&mut app, &raffle, &token, None);
+        }
+        assert_eq!(page(&app, None, Some(RaffleStatus::Cancelled), None, None, None), (vec![3], Some(99)));
+        assert_eq!(page(&app, Some(99), Some(RaffleStatus::Cancelled), None, None, None), (vec![], None));
+    }
+
+    fn player_stats(app: &App, raffle: &Addr, address: &str) -> PlayerStats {
+        app.wrap()
+            .query_wasm_smart(raffle, &QueryMsg::PlayerStats { address: address.to_string() })
+            .unwrap()
+    }
+
+    #[test]
+    fn player_history_and_stats() {
+        let (mut app, raffle, token) = setup();
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        begin_token_raffle(&mut app, &raffle, &token, None);
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        send_tokens(&mut app, PLAYER, &token, &raffle, 20, &ReceiveMsg::BuyTickets { id: 1 });
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::BuyTickets { id: 1 });
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 1 });
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 1 }, &[])
+            .unwrap();
+        end_raffle(&mut app, &raffle, 0);
+
+        let history = |start_after: Option<u32>| -> Vec<PlayerRaffle> {
+            app.wrap()
+                .query_wasm_smart(&raffle, &QueryMsg::PlayerRaffles { address: PLAYER.to_string(), start_after, limit: None })
+                .unwrap()
+        };
+        let tokens = |amount: u128| vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(amount) }];
+        assert_eq!(history(None), vec![
+            PlayerRaffle { raffle_id: 0, tickets: 1, paid: tokens(10), won: tokens(10) },
+            PlayerRaffle { raffle_id: 1, tickets: 3, paid: tokens(30), won: vec![] },
+        ]);
+        assert_eq!(history(Some(0)).len(), 1);
+
+        // the refunded raffle no longer counts
+        assert_eq!(player_stats(&app, &raffle, PLAYER), PlayerStats {
+            entries: 1,
+            tickets: 1,
+            staked: tokens(10),
+            wins: 1,
+            won: tokens(10),
+        });
+        assert_eq!(player_stats(&app, &raffle, SPONSOR).staked, tokens(0));
+        assert_eq!(player_stats(&app, &raffle, ADMIN), PlayerStats::default());
+    }
+
+    #[test]
+    fn simulate_entry_odds() {
+        let (mut app, raffle, token) = setup();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![3, 1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Cw20(token.clone()), ticket_price: Uint128::new(10) }],
+                series: None,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::JoinRaffleRound { id: 0 });
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 30, &ReceiveMsg::BuyTickets { id: 0 });
+
+        let simulation: SimulateEntryResponse = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::SimulateEntry { id: 0, address: PLAYER.to_string(), tickets: 4, denom: None })
+            .unwrap();
+        let tokens = |amount: u128| vec![AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(amount) }];
+        let odds = |tickets: u32, total_tickets: u32, prizes: [u128; 2], expected: u128| EntryOdds {
+            tickets,
+            total_tickets,
+            tiers: prizes
+                .iter()
+                .map(|prize| TierOdds { prize: tokens(*prize), chance: Decimal::from_ratio(tickets, total_tickets) })
+                .collect(),
+            jackpot_chance: Decimal::zero(),
+            expected_payout: tokens(expected),
+        };
+        assert_eq!(simulation.cost, tokens(40)[0]);
+        assert_eq!(simulation.current, odds(1, 4, [30, 10], 10));
+        assert_eq!(simulation.after, odds(5, 8, [60, 20], 50));
+
+        let err = app
+            .wrap()
+            .query_wasm_smart::<SimulateEntryResponse>(&raffle, &QueryMsg::SimulateEntry {
+                id: 0,
+                address: PLAYER.to_string(),
+                tickets: 1,
+                denom: Some(Denom::Native("ujuno".to_string())),
+            })
+            .unwrap_err();
+        assert!(err.to_string().contains("Denom not accepted"));
+
+        // overflowing counts are an error, not a panic
+        let err = app
+            .wrap()
+            .query_wasm_smart::<SimulateEntryResponse>(&raffle, &QueryMsg::SimulateEntry { id: 0, address: PLAYER.to_string(), tickets: u32::MAX, denom: None })
+            .unwrap_err();
+        assert!(err.to_string().contains("Too many tickets"));
+
+        // native funds buy as many tickets as tokens do
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            raffle.clone(),
+            &ExecuteMsg::BeginRaffleRound {
+                expire_type: 0,
+                winners_distribution: vec![1],
+                prize_tiers: vec![],
+                accepted_assets: vec![AcceptedAsset { denom: Denom::Native("ujuno".to_string()), ticket_price: Uint128::new(10) }],
+                series: None,
+                ticket_nft: None,
+                claim: None,
+                unawarded: UnawardedPrize::ProRata,
+                dust: DustDestination::FirstWinner,
+            },
+            &[],
+        )
+        .unwrap();
+        let simulate = |tickets: u32| {
+            app.wrap().query_wasm_smart::<SimulateEntryResponse>(&raffle, &QueryMsg::SimulateEntry { id: 1, address: NATIVE_PLAYER.to_string(), tickets, denom: None })
+        };
+        assert_eq!(simulate(1).unwrap().after.total_tickets, 1);
+        assert_eq!(simulate(3).unwrap().after.total_tickets, 3);
+    }
+
+    #[test]
+    fn global_stats_and_leaderboard() {
+        let (mut app, raffle, token) = setup();
+        for _ in 0..4 {
+            begin_token_raffle(&mut app, &raffle, &token, None);
+        }
+        send_tokens(&mut app, PLAYER, &token, &raffle, 30, &ReceiveMsg::BuyTickets { id: 0 });
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 10, &ReceiveMsg::BuyTickets { id: 1 });
+        send_tokens(&mut app, PLAYER, &token, &raffle, 10, &ReceiveMsg::BuyTickets { id: 2 });
+        send_tokens(&mut app, SPONSOR, &token, &raffle, 40, &ReceiveMsg::BuyTickets { id: 3 });
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::CancelRaffleRound { id: 2 }, &[])
+            .unwrap();
+        end_raffle(&mut app, &raffle, 0);
+        end_raffle(&mut app, &raffle, 1);
+
+        let leaderboard = |app: &App, limit: Option<u32>| -> Vec<LeaderboardEntry> {
+            app.wrap()
+                .query_wasm_smart(&raffle, &QueryMsg::Leaderboard { denom: Denom::Cw20(token.clone()), limit })
+                .unwrap()
+        };
+        let tokens = |amount: u128| AssetAmount { denom: Denom::Cw20(token.clone()), amount: Uint128::new(amount) };
+        let entry = |address: &str, won: u128| LeaderboardEntry { address: Addr::unchecked(address), won: Uint128::new(won) };
+
+        // the refunded tickets of the cancelled raffle are no volume
+        let stats: GlobalStats = app.wrap().query_wasm_smart(&raffle, &QueryMsg::GlobalStats {}).unwrap();
+        assert_eq!(stats, GlobalStats {
+            raffles: 4,
+            volume: vec![tokens(80)],
+            paid_out: vec![tokens(40)],
+            biggest_wins: vec![BiggestWin { raffle_id: 0, winner: Addr::unchecked(PLAYER), prize: tokens(30) }],
+        });
+        assert_eq!(leaderboard(&app, None), vec![entry(PLAYER, 30), entry(SPONSOR, 10)]);
+        assert_eq!(leaderboard(&app, Some(1)), vec![entry(PLAYER, 30)]);
+
+        // winnings add up and the winner moves up the board
+        app.execute_contract(Addr::unchecked(ADMIN), raffle.clone(), &ExecuteMsg::EndRaffleRound { id: 3 }, &[])
+            .unwrap();
+        assert_eq!(leaderboard(&app, None), vec![entry(SPONSOR, 50), entry(PLAYER, 30)]);
+        let stats: GlobalStats = app.wrap().query_wasm_smart(&raffle, &QueryMsg::GlobalStats {}).unwrap();
+        assert_eq!(stats.paid_out, vec![tokens(80)]);
+        assert_eq!(stats.biggest_wins, vec![BiggestWin { raffle_id: 3, winner: Addr::unchecked(SPONSOR), prize: tokens(40) }]);
+
+        let other: Vec<LeaderboardEntry> = app
+            .wrap()
+            .query_wasm_smart(&raffle, &QueryMsg::Leaderboard { denom: Denom::Native("ujuno".to_string()), limit: None })
+            .unwrap();
+        assert!(other.is_empty());
+    }
+}
@@ -3,5 +3,6 @@ mod error;
 pub mod msg;
 pub mod state;
 pub mod rand;
+mod integration_tests;
 
 pub use crate::error::ContractError;
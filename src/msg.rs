@@ -1,11 +1,27 @@
-use cosmwasm_std::{Addr, Uint128};
-use cw20::Cw20ReceiveMsg;
+use cosmwasm_std::{to_binary, Addr, Binary, CosmosMsg, Decimal, StdResult, Timestamp, Uint128, WasmMsg};
+use cw20::{Cw20ReceiveMsg, Denom};
+use cw721::Cw721ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::state::{AssetAmount, ClaimConfig, DustDestination, JackpotConfig, PrizeTier, UnawardedPrize};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub admins: Vec<String>,
+    // native denoms and cw20 addresses raffles may be paid in, empty allows any
+    pub allowed_denoms: Vec<String>,
+    // receives unclaimed prizes of raffles configured to send them there
+    pub treasury: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct MigrateMsg {
+    // 0.1 had neither, the treasury is required then and the denoms default to ujuno
+    #[serde(default)]
+    pub treasury: Option<String>,
+    #[serde(default)]
+    pub allowed_denoms: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -13,27 +29,301 @@ pub struct InstantiateMsg {
 pub enum ExecuteMsg {
     BeginRaffleRound { 
         expire_type: u8,  // 0: 30 mins, 1: 1 hour, 2: 1 day, 3: 1 week
-        minimum_stake: Uint128,
+        // shares of the pot of each rank, use either this or prize_tiers
+        #[serde(default)]
         winners_distribution: Vec<u32>,
-        pay_token: Option<String>,
+        #[serde(default)]
+        prize_tiers: Vec<PrizeTier>,
+        accepted_assets: Vec<AcceptedAsset>,
+        series: Option<String>,
+        // cw721 collection, minter set to this contract, in which every ticket is minted
+        ticket_nft: Option<String>,
+        // winners claim their prize instead of being paid at the draw
+        claim: Option<ClaimConfig>,
+        // defaults to pro_rata
+        #[serde(default)]
+        unawarded: UnawardedPrize,
+        // defaults to first_winner
+        #[serde(default)]
+        dust: DustDestination,
     },
-    JoinRaffleRoundByJuno {
+    JoinRaffleRound {
         id: u32
     },
-    JoinRaffleRoundByToken (
+    Receive (
         Cw20ReceiveMsg
     ),
+    // Adds the native funds sent to the pot without entering the raffle
+    SponsorRaffle {id: u32},
     EndRaffleRound {id: u32},
+    // Pays the caller every prize they won and haven't claimed yet
+    ClaimPrize {id: u32},
+    // Handles prizes whose claim deadline has passed as the raffle's ClaimConfig says
+    ExpireClaims {id: u32},
+    // Draws a new winner for a rank whose claim has expired in a raffle configured to redraw
+    Redraw {id: u32, rank: u32},
+    // Refunds every contribution and returns NFT prizes to their depositors
+    CancelRaffleRound {id: u32},
+    // Compacts a raffle settled at least 30 days ago into a RaffleSummary and deletes up to limit of its
+    // entries and ticket ranges, called again until they're all gone
+    ArchiveRaffle {
+        id: u32,
+        limit: Option<u32>,
+    },
+    // Sends the next limit payouts or refunds of a raffle too large to settle in one transaction
+    ContinueSettlement {
+        id: u32,
+        limit: Option<u32>,
+    },
+    ReceiveNft (
+        Cw721ReceiveMsg
+    ),
+    UpdateAllowedDenoms {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    // Sends a failed payout again, the recipient may redirect it to another address
+    RetryPayout {
+        payout_id: u64,
+        recipient: Option<String>,
+    },
+    // Sets or removes the jackpot of a series, raffles keep the settings they began with
+    UpdateJackpot {
+        series: String,
+        config: Option<JackpotConfig>,
+    },
+    // Contracts notified with RaffleHookMsg on every lifecycle transition
+    AddHook { addr: String },
+    RemoveHook { addr: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AcceptedAsset {
+    pub denom: Denom,
+    // price of one ticket in this asset, which fixes the exchange rate between assets
+    pub ticket_price: Uint128,
+}
+
+// Extension of the tokens minted in a raffle's ticket_nft collection
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TicketMetadata {
+    pub raffle_id: u32,
+    pub ticket_number: u32,
+}
+
+// Messages carried inside Cw20ReceiveMsg.msg when tokens are sent to this contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    // Join with a single entry of one ticket per ticket_price sent, as JoinRaffleRound does for native funds
+    JoinRaffleRound { id: u32 },
+    // One ticket per ticket_price sent, may be called again by the same player
+    BuyTickets { id: u32 },
+    // Adds to the pot without entering the raffle
+    SponsorRaffle { id: u32 },
+    // Adds to the pool carried into the next raffle of the series
+    FundSeries { series: String },
+}
+
+// Messages carried inside Cw721ReceiveMsg.msg when NFTs are sent to this contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveNftMsg {
+    // The NFT goes to the winner of rank (0 for the first prize)
+    AddPrize { id: u32, rank: u32 },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    // GetCount returns the current count as a json-encoded number
+    // GetCount returns the number of raffles begun so far, which is the id of the next one
     GetCount {},
     // GetWinner {},
-    GetTotalDeposit {},
+    // Balance held by the contract in the given denom
+    GetTotalDeposit { denom: Denom },
     GetRaffleInfo { id: u32 },
+    // Raffles by id with the given status, accepting pay_token and begun by creator, whichever are set,
+    // reading at most 100 raffles a page
+    ListRaffles {
+        start_after: Option<u32>,
+        limit: Option<u32>,
+        status: Option<RaffleStatus>,
+        pay_token: Option<Denom>,
+        creator: Option<String>,
+        order: Option<ListOrder>,
+    },
+    // What is kept of an archived raffle
+    GetRaffleSummary { id: u32 },
+    GetAllowedDenoms {},
+    // Pot of a raffle broken down by asset
+    GetPot { id: u32 },
+    // Sponsors of a raffle with what they added, by address
+    GetSponsorships {
+        id: u32,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Players of a raffle with their tickets, by address
+    GetEntries {
+        id: u32,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    // Raffles a player entered, by raffle id
+    PlayerRaffles {
+        address: String,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    PlayerStats { address: String },
+    GlobalStats {},
+    // Top winners by the total they won in denom
+    Leaderboard { denom: Denom, limit: Option<u32> },
+    // Odds and expected payout of address in a raffle, now and after buying tickets more,
+    // paid in denom or the raffle's first accepted asset
+    SimulateEntry {
+        id: u32,
+        address: String,
+        tickets: u32,
+        denom: Option<Denom>,
+    },
+    GetHooks {},
+    // Jackpot currently waiting to be won in a series
+    GetJackpot { series: String },
+    GetJackpotConfig { series: String },
+    // Jackpots won in a series, by raffle id
+    GetJackpotHits {
+        series: String,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    // Payouts whose transfer failed and can be retried
+    GetFailedPayouts {
+        raffle_id: Option<u32>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+}
+
+// Lifecycle events sent to the registered hook contracts
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RaffleHookMsg {
+    Opened { id: u32 },
+    Entered { id: u32, player: String, tickets: u32 },
+    Settled { id: u32, winners: Vec<String> },
+    Cancelled { id: u32 },
+}
+
+impl RaffleHookMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = RaffleHookExecuteMsg::RaffleHook(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+// The execute message hook contracts must accept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RaffleHookExecuteMsg {
+    RaffleHook(RaffleHookMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RaffleStatus {
+    // taking entries
+    Open,
+    // past its end time, waiting for EndRaffleRound
+    Ended,
+    // payouts or refunds left to ContinueSettlement
+    Settling,
+    Settled,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ListOrder {
+    Ascending,
+    Descending,
+}
+
+impl Default for ListOrder {
+    fn default() -> Self {
+        ListOrder::Ascending
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListRafflesResponse {
+    pub raffles: Vec<RaffleListItem>,
+    // start_after of the next page, the last raffle read, None once there are no more to read
+    pub next_start_after: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RaffleListItem {
+    pub id: u32,
+    pub status: RaffleStatus,
+    pub begin_time_stamp: Timestamp,
+    pub end_time_stamp: Timestamp,
+    pub pot: Vec<AssetAmount>,
+    pub entrants: u32,
+}
+
+// A player's entry in a raffle and what they won in it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PlayerRaffle {
+    pub raffle_id: u32,
+    pub tickets: u32,
+    pub paid: Vec<AssetAmount>,
+    pub won: Vec<AssetAmount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LeaderboardEntry {
+    pub address: Addr,
+    pub won: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateEntryResponse {
+    pub cost: AssetAmount,
+    pub current: EntryOdds,
+    pub after: EntryOdds,
+}
+
+// Every rank is drawn from all tickets, so a player may win several ranks, with a chance of tickets / total_tickets each
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EntryOdds {
+    pub tickets: u32,
+    pub total_tickets: u32,
+    pub tiers: Vec<TierOdds>,
+    // of also taking the series jackpot with the first rank
+    pub jackpot_chance: Decimal,
+    // prizes and jackpot weighted by their chances, after the jackpot share is taken from the pot
+    pub expected_payout: Vec<AssetAmount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TierOdds {
+    // what each winner of the tier gets if the draw happened now
+    pub prize: Vec<AssetAmount>,
+    // of winning at least one of the tier's ranks
+    pub chance: Decimal,
 }
 
 // We define a custom struct for each query response
@@ -2,8 +2,9 @@ use cosmwasm_std::BlockInfo;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use cosmwasm_std::{Addr, Timestamp, Uint128};
-use cw_storage_plus::Item;
-use cw_storage_plus::Map;
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use cw_controllers::Hooks;
+use cw20::Denom;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Raffle
@@ -11,35 +12,399 @@ pub struct Raffle
     pub id : u32,
     pub begin_time_stamp : Timestamp,
     pub end_time_stamp : Timestamp,
-    pub minimum_stake : Uint128, // Size per slot
     pub winners_distribution: Vec<u32>,
-    pub players: Vec<String>,
-    pub winner_payouts: Vec<Uint128>,
+    // players and their tickets are kept in entries() and TICKET_RANGES, these count them
+    #[serde(default)]
+    pub entrants: u32,
+    #[serde(default)]
+    pub tickets: u32,
+    pub winner_payouts: Vec<Vec<AssetAmount>>, // per winner, one entry per asset with a pot
     pub winners : Vec<String>,
     pub active: bool,
-    pub pay_token: Option<String>,
+    pub assets: Vec<RaffleAsset>,
+    pub series: Option<String>,
+    pub creator: Addr,
+    pub nft_prizes: Vec<NftPrize>,
+    pub cancelled: bool,
+    pub ticket_nft: Option<Addr>,
+    pub claim: Option<ClaimConfig>,
+    pub pending_claims: Vec<PendingClaim>, // ranks drawn but not yet claimed, in claim mode
+    pub redraws: Vec<RedrawRecord>, // audit log of forfeited winners and who replaced them
+    pub jackpot: Option<JackpotConfig>, // the series' jackpot settings when the raffle began
+    pub jackpot_contributions: Vec<AssetAmount>, // taken from the pot into the series jackpot at the draw
+    pub unawarded: UnawardedPrize,
+    pub unawarded_amounts: Vec<AssetAmount>, // shares of ranks left without a winner, sent where unawarded says
+    #[serde(default)]
+    pub sponsors: u32, // sponsors are kept in SPONSORSHIPS, this counts them
+    pub prize_tiers: Vec<PrizeTier>, // what each rank wins, winners_distribution as shares when not given
+    pub dust: DustDestination,
+    pub dust_amounts: Vec<AssetAmount>, // rounding remainder of the share split, sent where dust says
+    #[serde(default)]
+    pub settlement: Option<Settlement>, // payouts or refunds still to send, in batches through ContinueSettlement
+    #[serde(default)]
+    pub settled_at: Option<Timestamp>, // when the winners were drawn or the raffle cancelled
+    #[serde(default)]
+    pub seed_hash: Option<String>, // hex sha256 of the seed the winners were drawn with
+    #[serde(default)]
+    pub seeded: Vec<AssetAmount>, // carried over from the series pool into the pots, returned to it on cancel
 }
 
 impl Raffle {
     pub fn is_expired(&self, block: &BlockInfo) -> bool {
         self.end_time_stamp <= block.time
     }
+
+    // token id of a ticket in the ticket_nft collection, ticket_number counts from 0 in order of purchase
+    pub fn ticket_token_id(&self, ticket_number: u32) -> String {
+        format!("{}-{}", self.id, ticket_number)
+    }
+
+    pub fn asset_index(&self, denom: &Denom) -> Option<usize> {
+        self.assets.iter().position(|asset| asset.denom == *denom)
+    }
+
+    // The tier of every rank, in rank order
+    pub fn rank_tiers(&self) -> Vec<&PrizeTier> {
+        self.prize_tiers
+            .iter()
+            .flat_map(|tier| std::iter::repeat(tier).take(tier.winners as usize))
+            .collect()
+    }
+
+    pub fn rank_count(&self) -> usize {
+        self.prize_tiers.iter().map(|tier| tier.winners as usize).sum()
+    }
+}
+
+// Running totals of a player over all raffles, kept as they enter, get refunded and get paid
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct PlayerStats
+{
+    // raffles entered, tickets and what was paid for them, less cancelled raffles once refunded
+    pub entries: u32,
+    pub tickets: u32,
+    pub staked: Vec<AssetAmount>,
+    pub wins: u32, // ranks paid out, in claim mode once claimed
+    pub won: Vec<AssetAmount>, // prizes received, including failed payouts once retried
+}
+
+// All-time totals of the contract, kept as raffles begin, tickets are bought or refunded and prizes are paid
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct GlobalStats
+{
+    pub raffles: u32, // begun, including those migrated from 0.1
+    pub volume: Vec<AssetAmount>, // paid for tickets, less refunds of cancelled raffles
+    pub paid_out: Vec<AssetAmount>, // prizes received by their winners, including failed payouts once retried
+    pub biggest_wins: Vec<BiggestWin>, // one per denom
 }
 
+// The largest prize of a single rank paid in a denom, jackpot included
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BiggestWin
+{
+    pub raffle_id: u32,
+    pub winner: Addr,
+    pub prize: AssetAmount,
+}
 
+// What is kept of a raffle once archived, its entries and ticket ranges are deleted
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct JoinInfo {
+pub struct RaffleSummary
+{
     pub id: u32,
+    pub begin_time_stamp: Timestamp,
+    pub end_time_stamp: Timestamp,
+    pub settled_at: Timestamp,
+    pub cancelled: bool,
+    pub series: Option<String>,
+    pub entrants: u32,
+    pub tickets: u32,
+    pub pot: Vec<AssetAmount>,
+    pub winners: Vec<String>,
+    pub winner_payouts: Vec<Vec<AssetAmount>>,
+    pub seed_hash: Option<String>,
+    // entries or ticket ranges are left to delete, ArchiveRaffle goes on with them
+    pub pruning: bool,
+}
+
+// A player's entry in an archived raffle, kept for their history once the entry is pruned
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArchivedEntry
+{
+    pub tickets: u32,
+    pub paid: Vec<AssetAmount>,
+    pub won: Vec<AssetAmount>,
+}
+
+// Progress of paying a raffle's winners, or when cancelled refunding its players then sponsors
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Settlement
+{
+    pub done: u32,
+    pub total: u32,
+    // refunds go through players then sponsors by address, the next batch starts after this one
+    pub refunded_after: Option<Addr>,
+}
+
+impl Settlement {
+    pub fn remaining(&self) -> u32 {
+        self.total - self.done
+    }
+}
+
+// Consecutive ranks winning the same prize
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PrizeTier
+{
+    pub winners: u32,
+    pub prize: TierPrize,
+}
+
+// Fixed prizes are paid first, then percentages of what is left, and shares split the rest
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TierPrize {
+    // paid to each winner in rank order while the pot covers it, the first one it can't cover in full gets what's left
+    Fixed { amounts: Vec<AssetAmount> },
+    // basis points of the pot left after fixed prizes, split evenly among the tier's winners
+    Percent { bps: u32 },
+    // shares of the pot left after fixed and percentage prizes, for each winner of the tier
+    Shares { shares: u32 },
+}
+
+// Winnings are held after the draw until the winner claims them with ClaimPrize
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimConfig
+{
+    pub period: u64, // seconds a winner has to claim after the draw
+    pub unclaimed: UnclaimedPrize,
+}
+
+// Where a prize goes once its claim deadline has passed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnclaimedPrize {
+    Treasury,
+    // into the pool of the raffle's series, seeding its next raffle
+    Rollover,
+    // the winner forfeits, a new one is drawn for the rank among the other players and gets a new claim period
+    Redraw,
+}
+
+// Where the prizes of ranks go when there are fewer players than ranks
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnawardedPrize {
+    // left in the pot, so the actual winners' shares of it grow, needs a share tier
+    ProRata,
+    // into the pool of the raffle's series, seeding its next raffle
+    NextRound,
+    // also where ProRata sends them when nobody played
+    Treasury,
+}
+
+impl Default for UnawardedPrize {
+    fn default() -> Self {
+        UnawardedPrize::ProRata
+    }
+}
+
+// Where the remainder of dividing a pot into shares goes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DustDestination {
+    // to the treasury when nobody won
+    FirstWinner,
+    Treasury,
+    // into the pool of the raffle's series, seeding its next raffle
+    NextRound,
+}
+
+impl Default for DustDestination {
+    fn default() -> Self {
+        DustDestination::FirstWinner
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingClaim
+{
+    pub rank: u32,
+    pub deadline: Timestamp,
+}
+
+// An accepted payment asset and what has been contributed in it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RaffleAsset
+{
+    pub denom: Denom,
+    pub ticket_price: Uint128, // Size per slot
+    pub pot: Uint128,
+}
+
+// A player's tickets in a raffle and what they paid for them, refunded if the raffle is cancelled
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Entry
+{
+    pub raffle_id: u32,
+    pub player: Addr,
+    pub tickets: u32,
+    pub paid: Vec<AssetAmount>,
+}
+
+// Funds added to the pot by someone who isn't playing, one amount per asset, refunded if the raffle is cancelled
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Sponsorship
+{
+    pub raffle_id: u32,
+    pub sponsor: Addr,
+    pub amounts: Vec<AssetAmount>,
+}
+
+// A cw721 token held by the contract as the prize for a rank
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NftPrize
+{
+    pub contract: Addr,
+    pub token_id: String,
+    pub rank: u32,
+    pub depositor: Addr,
+}
+
+// A transfer of winnings or a refund sent as a submessage, kept until it succeeds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Payout
+{
+    pub id: u64,
+    pub raffle_id: u32,
+    pub recipient: String,
+    pub denom: Denom,
+    pub amount: Uint128,
+    // the winner whose prize this is, credited to their stats once it arrives, None for other transfers
+    #[serde(default)]
+    pub winner: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetAmount
+{
+    pub denom: Denom,
+    pub amount: Uint128,
+}
+
+
+// Number of raffles begun so far, which is also the id of the next one
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Counter
 {
     pub counter: u32,
 }
 
+// A share of the pot of every raffle in a series feeds its jackpot, which the first winner takes with a chance of 1 in odds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JackpotConfig
+{
+    pub share_bps: u16, // basis points of each pot
+    pub odds: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JackpotHit
+{
+    pub raffle_id: u32,
+    pub winner: String,
+    pub amounts: Vec<AssetAmount>,
+    pub time: Timestamp,
+}
+
+// A winner who forfeited an unclaimed prize and the winner drawn in their place
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RedrawRecord
+{
+    pub rank: u32,
+    pub forfeited: String,
+    pub winner: Option<String>, // None when nobody but forfeited winners was left and the prize went to the treasury
+    pub time: Timestamp,
+    pub seed_hash: String,
+}
+
 pub const STATE: Item<Raffle> = Item::new("raffle");
 pub const COUNTER: Item<Counter> = Item::new("counter");
-pub const RAFFLEMAP: Map<&str, Raffle> = Map::new("escrow");
+pub const RAFFLEMAP: Map<u32, Raffle> = Map::new("raffles");
+// archived raffles, removed from RAFFLEMAP
+pub const ARCHIVE: Map<u32, RaffleSummary> = Map::new("archive");
+pub const PLAYER_STATS: Map<&Addr, PlayerStats> = Map::new("player_stats");
+// entries moved out of entries() by ArchiveRaffle, by player and raffle id
+pub const PLAYER_ARCHIVE: Map<(&Addr, u32), ArchivedEntry> = Map::new("player_archive");
+pub const GLOBAL_STATS: Item<GlobalStats> = Item::new("global_stats");
+// winnings of every winner keyed by token_key of the denom and amount, to read them from the highest
+pub const LEADERBOARD: Map<(&str, u128, &Addr), Uint128> = Map::new("leaderboard");
 pub const ADMINS: Item<Vec<Addr>> = Item::new("admins");
-pub const PLAYERS: Item<Vec<Addr>> = Item::new("players");
\ No newline at end of file
+pub const TREASURY: Item<Addr> = Item::new("treasury");
+// empty only when instantiated without an allowlist, which allows any denom
+pub const ALLOWED_DENOMS: Item<Vec<String>> = Item::new("allowed_denoms");
+pub const HOOKS: Hooks = Hooks::new("hooks");
+pub const PAYOUT_COUNT: Item<u64> = Item::new("payout_count");
+// payouts sent and waiting for their reply
+pub const PENDING_PAYOUTS: Map<u64, Payout> = Map::new("pending_payouts");
+// payouts whose transfer failed, paid again through RetryPayout
+pub const FAILED_PAYOUTS: Map<u64, Payout> = Map::new("failed_payouts");
+pub const PLAYERS: Item<Vec<Addr>> = Item::new("players");
+// A raffle as 0.1 stored it, paid in ujuno or a single cw20 and with its players inline
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyRaffle
+{
+    pub id: u32,
+    pub begin_time_stamp: Timestamp,
+    pub end_time_stamp: Timestamp,
+    pub minimum_stake: Uint128,
+    pub winners_distribution: Vec<u32>,
+    pub players: Vec<String>,
+    pub winner_payouts: Vec<Uint128>,
+    pub winners: Vec<String>,
+    pub active: bool,
+    pub pay_token: Option<String>,
+}
+
+// 0.1 raffles keyed by their id as a string, which sorted "10" before "2", moved to RAFFLEMAP by migrate
+pub const LEGACY_RAFFLES: Map<&str, LegacyRaffle> = Map::new("escrow");
+
+// (raffle id, tickets sold up to and including a purchase) -> buyer. A purchase holds the tickets from the
+// previous key up to its own, so the holder of a ticket is found in one range read as the first key above it.
+pub const TICKET_RANGES: Map<(u32, u32), Addr> = Map::new("ticket_ranges");
+// (raffle id, sponsor) -> what they added to the pots
+pub const SPONSORSHIPS: Map<(u32, &Addr), Sponsorship> = Map::new("sponsorships");
+
+pub struct EntryIndexes<'a> {
+    pub player: MultiIndex<'a, Addr, Entry, (u32, Addr)>,
+}
+
+impl<'a> IndexList<Entry> for EntryIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Entry>> + '_> {
+        let v: Vec<&dyn Index<Entry>> = vec![&self.player];
+        Box::new(v.into_iter())
+    }
+}
+
+// (raffle id, player) -> entry, indexed by player
+pub fn entries<'a>() -> IndexedMap<'a, (u32, &'a Addr), Entry, EntryIndexes<'a>> {
+    let indexes = EntryIndexes {
+        player: MultiIndex::new(|entry| entry.player.clone(), "entries", "entries__player"),
+    };
+    IndexedMap::new("entries", indexes)
+}
+// (series, pay token) -> amount carried into the next raffle of the series
+pub const SERIES_POOLS: Map<(&str, &str), Uint128> = Map::new("series_pools");
+pub const JACKPOT_CONFIGS: Map<&str, JackpotConfig> = Map::new("jackpot_configs");
+// series -> jackpot waiting to be won
+pub const JACKPOTS: Map<&str, Vec<AssetAmount>> = Map::new("jackpots");
+// (series, raffle id) -> jackpot won in that raffle
+pub const JACKPOT_HITS: Map<(&str, u32), JackpotHit> = Map::new("jackpot_hits");
+
+// Key used for a pay token in SERIES_POOLS and ALLOWED_DENOMS: the native denom or the cw20 address
+pub fn token_key(pay_token: &Denom) -> String {
+    match pay_token {
+        Denom::Native(denom) => denom.clone(),
+        Denom::Cw20(addr) => addr.to_string(),
+    }
+}